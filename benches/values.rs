@@ -0,0 +1,51 @@
+//! Times the VM on an arithmetic-heavy and a call-heavy script. Run it
+//! with and without the `nan_boxing` feature to compare the two value
+//! representations:
+//!
+//!     cargo bench --bench values
+//!     cargo bench --bench values --features nan_boxing
+
+use std::time::Instant;
+
+use rlox::chunk::Chunk;
+use rlox::compiler::{Compiler, OptLevel};
+use rlox::value::Value;
+use rlox::vm::{InterpretResult, VM};
+
+const RUNS: u32 = 20_000;
+
+fn identity(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    Ok(args[0].clone())
+}
+
+// Constant folding would leave nothing to run, so compile as written.
+fn compile(source: &str) -> Chunk {
+    Compiler::with_opt_level(String::from(source), OptLevel::None).compile().unwrap()
+}
+
+fn bench(name: &str, chunk: &Chunk) {
+    let mut vm: VM = Default::default();
+    vm.define_native("f", 1, identity);
+    let mut out = Vec::new();
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        out.clear();
+        assert_eq!(vm.interpret_chunk(chunk.clone(), &mut out), InterpretResult::Ok);
+    }
+    let elapsed = start.elapsed();
+
+    println!("{:12} {:>8.2} µs/run", name, elapsed.as_secs_f64() * 1e6 / RUNS as f64);
+}
+
+fn main() {
+    let representation = if cfg!(feature = "nan_boxing") { "nan-boxed" } else { "tagged enum" };
+    println!("values: {}", representation);
+
+    // Chunks hold at most 256 constants, which bounds both scripts.
+    let arithmetic: Vec<String> = (0..60).map(|n| format!("({} + {}) * {} - {}", n, n + 1, n + 2, n + 3)).collect();
+    bench("arithmetic", &compile(&arithmetic.join(" / ")));
+
+    let calls = format!("{}1{}", "f(".repeat(250), ")".repeat(250));
+    bench("calls", &compile(&calls));
+}
@@ -0,0 +1,113 @@
+use super::*;
+use crate::chunk::*;
+use crate::compiler::diagnostic::*;
+use crate::compiler::fold::*;
+use crate::compiler::peephole;
+use crate::value::*;
+
+/// Compiles an expression tree to the same bytecode the single-pass
+/// compiler produces for its source at the default optimisation level.
+pub fn generate(expr: &Expr) -> Result<Chunk, Diagnostic> {
+    let mut generator: Generator = Default::default();
+
+    generator.expr(expr)?;
+    generator.chunk.write_chunk(OpCode::Return, expr.line);
+    peephole::optimize(&mut generator.chunk);
+    Ok(generator.chunk)
+}
+
+#[derive(Default)]
+struct Generator {
+    chunk: Chunk
+}
+
+impl Generator {
+    fn expr(&mut self, expr: &Expr) -> Result<(), Diagnostic> {
+        match &expr.kind {
+            ExprKind::Number(number) => self.constant(Value::Number(*number), expr.line)?,
+            ExprKind::Integer(int) => self.constant(Value::Int(*int), expr.line)?,
+            ExprKind::String(string) => self.constant(Value::String(string.as_str().into()), expr.line)?,
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    self.expr(part)?;
+                }
+                write_build_string(&mut self.chunk, parts.len(), expr.line);
+            }
+            ExprKind::Grouping(inner) => self.expr(inner)?,
+            ExprKind::Unary(operator, operand) => {
+                self.expr(operand)?;
+                match operator {
+                    UnaryOp::Negate => write_operator(&mut self.chunk, OpCode::Negate, expr.line),
+                    UnaryOp::BitNot => write_operator(&mut self.chunk, OpCode::BitNot, expr.line)
+                }
+            }
+            ExprKind::Binary(operator, left, right) => {
+                self.expr(left)?;
+                self.expr(right)?;
+                let instruction = match operator {
+                    BinaryOp::Add => OpCode::Add,
+                    BinaryOp::Subtract => OpCode::Subtract,
+                    BinaryOp::Multiply => OpCode::Multiply,
+                    BinaryOp::Divide => OpCode::Divide,
+                    BinaryOp::Modulo => OpCode::Modulo,
+                    BinaryOp::IntDivide => OpCode::IntDivide,
+                    BinaryOp::Power => OpCode::Power,
+                    BinaryOp::BitAnd => OpCode::BitAnd,
+                    BinaryOp::BitOr => OpCode::BitOr,
+                    BinaryOp::BitXor => OpCode::BitXor,
+                    BinaryOp::ShiftLeft => OpCode::ShiftLeft,
+                    BinaryOp::ShiftRight => OpCode::ShiftRight
+                };
+                write_operator(&mut self.chunk, instruction, expr.line);
+            }
+            ExprKind::Variable(name) => {
+                let index = self.make_constant(Value::String(name.as_str().into()), expr.line)?;
+                self.emit(OpCode::GetGlobal, expr.line);
+                self.emit(OpCode::OpArg(index), expr.line);
+            }
+            ExprKind::Call(callee, args) => {
+                self.expr(callee)?;
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                self.emit(OpCode::Call, expr.line);
+                self.emit(OpCode::OpArg(args.len()), expr.line);
+            }
+            ExprKind::Invoke(receiver, name, args) => {
+                self.expr(receiver)?;
+                let index = self.make_constant(Value::String(name.as_str().into()), expr.line)?;
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                self.emit(OpCode::Invoke, expr.line);
+                self.emit(OpCode::OpArg(index), expr.line);
+                self.emit(OpCode::OpArg(args.len()), expr.line);
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, instruction: OpCode, line: usize) {
+        self.chunk.write_chunk(instruction, line);
+    }
+
+    fn constant(&mut self, value: Value, line: usize) -> Result<(), Diagnostic> {
+        let index = self.make_constant(value, line)?;
+        self.emit(OpCode::Constant, line);
+        self.emit(OpCode::OpArg(index), line);
+        Ok(())
+    }
+
+    fn make_constant(&mut self, value: Value, line: usize) -> Result<usize, Diagnostic> {
+        let index = self.chunk.add_constant(value);
+        if index > u8::MAX as usize {
+            return Err(Diagnostic {
+                severity: Severity::Error,
+                line,
+                location: None,
+                message: String::from("Too many constants in one chunk.")
+            });
+        }
+        Ok(index)
+    }
+}
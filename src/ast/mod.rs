@@ -0,0 +1,92 @@
+use crate::scanner::Span;
+
+/// An expression parsed from source, for tools that need to look at or
+/// rewrite a program before it becomes bytecode. `span` covers the
+/// expression's source text and `line` is the line of its last token, which
+/// is where the single-pass compiler attributes its instructions.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+    pub line: usize
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub enum ExprKind {
+    Number(f64),
+    Integer(i64),
+    String(String),
+    // The string parts and the expressions between them, in source order.
+    Interpolation(Vec<Expr>),
+    Grouping(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Variable(String),
+    // The callee and its arguments.
+    Call(Box<Expr>, Vec<Expr>),
+    // The receiver, the method name and the arguments.
+    Invoke(Box<Expr>, String, Vec<Expr>)
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Copy, Clone)]
+pub enum UnaryOp {
+    Negate,
+    BitNot
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Copy, Clone)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    IntDivide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight
+}
+
+impl UnaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Negate => "-",
+            UnaryOp::BitNot => "~"
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+            BinaryOp::IntDivide => "~/",
+            BinaryOp::Power => "**",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>"
+        }
+    }
+}
+
+pub mod parser;
+pub mod printer;
+pub mod codegen;
+
+#[cfg(test)]
+mod test;
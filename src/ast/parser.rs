@@ -0,0 +1,286 @@
+use super::*;
+use crate::compiler::diagnostic::*;
+use crate::compiler::parse_number;
+use crate::value::Value;
+use crate::scanner::*;
+
+/// Parses source into an expression tree, accepting the same language as
+/// the single-pass compiler and reporting the same errors.
+pub fn parse(source: String) -> Result<Expr, Vec<Diagnostic>> {
+    let mut parser = Parser::new(source);
+    parser.advance();
+
+    let result = parser.expression().and_then(|expr| {
+        if parser.current.token_type != TokenType::EOF {
+            return Err(parser.error_at_current("Expect end of expression."));
+        }
+        Ok(expr)
+    });
+
+    // Like the compiler, stay quiet about what follows a scan error.
+    if !parser.diagnostics.is_empty() {
+        return Err(parser.diagnostics);
+    }
+    result.map_err(|diagnostic| vec![diagnostic])
+}
+
+struct Parser {
+    scanner: Scanner,
+    current: Token,
+    previous: Token,
+    // Scan errors skipped over while advancing.
+    diagnostics: Vec<Diagnostic>
+}
+
+// Binding power of each left-associative binary operator, tightest last.
+// `**` binds tighter than the unary operators and is parsed apart.
+fn binary_operator(token_type: TokenType) -> Option<(u8, BinaryOp)> {
+    match token_type {
+        TokenType::Pipe => Some((1, BinaryOp::BitOr)),
+        TokenType::Caret => Some((2, BinaryOp::BitXor)),
+        TokenType::Ampersand => Some((3, BinaryOp::BitAnd)),
+        TokenType::LessLess => Some((4, BinaryOp::ShiftLeft)),
+        TokenType::GreaterGreater => Some((4, BinaryOp::ShiftRight)),
+        TokenType::Plus => Some((5, BinaryOp::Add)),
+        TokenType::Minus => Some((5, BinaryOp::Subtract)),
+        TokenType::Star => Some((6, BinaryOp::Multiply)),
+        TokenType::Slash => Some((6, BinaryOp::Divide)),
+        TokenType::Percent => Some((6, BinaryOp::Modulo)),
+        TokenType::TildeSlash => Some((6, BinaryOp::IntDivide)),
+        _ => None
+    }
+}
+
+impl Parser {
+    fn new(source: String) -> Self {
+        Self {
+            scanner: Scanner::new(source),
+            current: Default::default(),
+            previous: Default::default(),
+            diagnostics: Vec::new()
+        }
+    }
+
+    fn advance(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+
+        for token in self.scanner.by_ref() {
+            if token.token_type != TokenType::Error {
+                self.current = token;
+                return;
+            }
+            self.diagnostics.push(Diagnostic::at(Severity::Error, &token, token.lexeme.clone()));
+        }
+        self.current = self.scanner.eof();
+    }
+
+    fn error(&self, message: &str) -> Diagnostic {
+        Diagnostic::at(Severity::Error, &self.previous, String::from(message))
+    }
+
+    fn error_at_current(&self, message: &str) -> Diagnostic {
+        Diagnostic::at(Severity::Error, &self.current, String::from(message))
+    }
+
+    fn expression(&mut self) -> Result<Expr, Diagnostic> {
+        self.binary(1)
+    }
+
+    // Parses operators binding at least as tightly as `power`, grouping
+    // operators of equal power to the left.
+    fn binary(&mut self, power: u8) -> Result<Expr, Diagnostic> {
+        let mut left = self.unary()?;
+
+        while let Some((operator_power, operator)) = binary_operator(self.current.token_type) {
+            if operator_power < power {
+                break;
+            }
+            self.advance();
+
+            let right = self.binary(operator_power + 1)?;
+            left = Expr {
+                span: Span { start: left.span.start, end: right.span.end },
+                line: right.line,
+                kind: ExprKind::Binary(operator, Box::new(left), Box::new(right))
+            };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Diagnostic> {
+        let operator = match self.current.token_type {
+            TokenType::Minus => UnaryOp::Negate,
+            TokenType::Tilde => UnaryOp::BitNot,
+            _ => return self.power()
+        };
+
+        self.advance();
+        let start = self.previous.span.start;
+        let operand = self.unary()?;
+
+        Ok(Expr {
+            span: Span { start, end: operand.span.end },
+            line: operand.line,
+            kind: ExprKind::Unary(operator, Box::new(operand))
+        })
+    }
+
+    // `**` groups to the right, and its exponent may carry a sign.
+    fn power(&mut self) -> Result<Expr, Diagnostic> {
+        let base = self.call()?;
+        if self.current.token_type != TokenType::StarStar {
+            return Ok(base);
+        }
+
+        self.advance();
+        let exponent = self.unary()?;
+        Ok(Expr {
+            span: Span { start: base.span.start, end: exponent.span.end },
+            line: exponent.line,
+            kind: ExprKind::Binary(BinaryOp::Power, Box::new(base), Box::new(exponent))
+        })
+    }
+
+    fn call(&mut self) -> Result<Expr, Diagnostic> {
+        let mut expr = self.primary()?;
+
+        loop {
+            let start = expr.span.start;
+            let kind = match self.current.token_type {
+                TokenType::LeftParen => {
+                    self.advance();
+                    ExprKind::Call(Box::new(expr), self.arguments()?)
+                }
+                // Like the compiler, only method calls may follow a dot.
+                TokenType::Dot => {
+                    self.advance();
+                    if self.current.token_type != TokenType::Identifier {
+                        return Err(self.error_at_current("Expect property name after '.'."));
+                    }
+                    self.advance();
+                    let name = self.previous.lexeme.clone();
+
+                    if self.current.token_type != TokenType::LeftParen {
+                        return Err(self.error_at_current("Expect '(' after method name."));
+                    }
+                    self.advance();
+                    ExprKind::Invoke(Box::new(expr), name, self.arguments()?)
+                }
+                _ => return Ok(expr)
+            };
+
+            expr = Expr {
+                span: Span { start, end: self.previous.span.end },
+                line: self.previous.line,
+                kind
+            };
+        }
+    }
+
+    // Parses the arguments of a call, up to and including its `)`.
+    fn arguments(&mut self) -> Result<Vec<Expr>, Diagnostic> {
+        let mut args = Vec::new();
+
+        if self.current.token_type != TokenType::RightParen {
+            loop {
+                args.push(self.expression()?);
+                if args.len() > 255 {
+                    return Err(self.error("Can't have more than 255 arguments."));
+                }
+                if self.current.token_type != TokenType::Comma {
+                    break;
+                }
+                self.advance();
+            }
+        }
+        if self.current.token_type != TokenType::RightParen {
+            return Err(self.error_at_current("Expect ')' after arguments."));
+        }
+        self.advance();
+        Ok(args)
+    }
+
+    fn primary(&mut self) -> Result<Expr, Diagnostic> {
+        self.advance();
+        let token = &self.previous;
+
+        let kind = match token.token_type {
+            TokenType::Number => match parse_number(&token.lexeme).map_err(|message| self.error(&message))? {
+                Value::Int(int) => ExprKind::Integer(int),
+                Value::Number(number) => ExprKind::Number(number),
+                _ => unreachable!()
+            },
+            TokenType::String => {
+                let lexeme = &token.lexeme;
+                if lexeme.starts_with("\"\"\"") {
+                    ExprKind::String(String::from(&lexeme[3..lexeme.len() - 3]))
+                } else {
+                    ExprKind::String(self.string_part(1)?)
+                }
+            }
+            TokenType::Interpolation => return self.interpolation(),
+            TokenType::Identifier => ExprKind::Variable(token.lexeme.clone()),
+            TokenType::LeftParen => {
+                let start = token.span.start;
+                let expr = self.expression()?;
+                if self.current.token_type != TokenType::RightParen {
+                    return Err(self.error_at_current("Expect ')' after expression."));
+                }
+                self.advance();
+
+                return Ok(Expr {
+                    span: Span { start, end: self.previous.span.end },
+                    line: self.previous.line,
+                    kind: ExprKind::Grouping(Box::new(expr))
+                });
+            }
+            _ => return Err(self.error("Expect expression."))
+        };
+
+        Ok(self.literal(kind))
+    }
+
+    fn interpolation(&mut self) -> Result<Expr, Diagnostic> {
+        let start = self.previous.span.start;
+        let mut parts = Vec::new();
+
+        loop {
+            let part = ExprKind::String(self.string_part(2)?);
+            parts.push(self.literal(part));
+            parts.push(self.expression()?);
+
+            if self.current.token_type != TokenType::Interpolation {
+                break;
+            }
+            self.advance();
+        }
+
+        if self.current.token_type != TokenType::String || !self.current.lexeme.starts_with('}') {
+            return Err(self.error_at_current("Expect end of string interpolation."));
+        }
+        self.advance();
+        let part = ExprKind::String(self.string_part(1)?);
+        parts.push(self.literal(part));
+
+        Ok(Expr {
+            span: Span { start, end: self.previous.span.end },
+            line: self.previous.line,
+            kind: ExprKind::Interpolation(parts)
+        })
+    }
+
+    // Decodes the previous string token, minus its opening delimiter and
+    // `closing` bytes of closing delimiter.
+    fn string_part(&self, closing: usize) -> Result<String, Diagnostic> {
+        let lexeme = &self.previous.lexeme;
+        unescape(&lexeme[1..lexeme.len() - closing]).map_err(|message| self.error(&message))
+    }
+
+    fn literal(&self, kind: ExprKind) -> Expr {
+        Expr {
+            kind,
+            span: self.previous.span,
+            line: self.previous.line
+        }
+    }
+}
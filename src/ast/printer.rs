@@ -0,0 +1,39 @@
+use super::*;
+use crate::value::Value;
+
+/// Prints an expression as a fully parenthesized prefix form, such as
+/// `(* (group (+ (- 1) 2.5)) (call f 3))`, which makes its structure explicit.
+pub fn print(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Number(number) => Value::Number(*number).to_string(),
+        ExprKind::Integer(int) => int.to_string(),
+        ExprKind::String(string) => format!("{:?}", string),
+        ExprKind::Interpolation(parts) => {
+            parenthesize("interpolate", &parts.iter().collect::<Vec<_>>())
+        }
+        ExprKind::Grouping(inner) => parenthesize("group", &[inner]),
+        ExprKind::Unary(operator, operand) => parenthesize(operator.symbol(), &[operand]),
+        ExprKind::Binary(operator, left, right) => parenthesize(operator.symbol(), &[left, right]),
+        ExprKind::Variable(name) => name.clone(),
+        ExprKind::Call(callee, args) => {
+            let mut exprs: Vec<&Expr> = vec![callee];
+            exprs.extend(args);
+            parenthesize("call", &exprs)
+        }
+        ExprKind::Invoke(receiver, name, args) => {
+            let mut exprs: Vec<&Expr> = vec![receiver];
+            exprs.extend(args);
+            parenthesize(&format!("invoke {}", name), &exprs)
+        }
+    }
+}
+
+fn parenthesize(name: &str, exprs: &[&Expr]) -> String {
+    let mut result = format!("({}", name);
+    for expr in exprs {
+        result.push(' ');
+        result.push_str(&print(expr));
+    }
+    result.push(')');
+    result
+}
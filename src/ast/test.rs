@@ -0,0 +1,76 @@
+use super::codegen::generate;
+use super::parser::parse;
+use super::printer::print;
+use super::*;
+use crate::compiler::Compiler;
+
+fn printed(source: &str) -> String {
+    print(&parse(String::from(source)).unwrap())
+}
+
+#[test]
+fn parses_expressions() {
+    assert_eq!(printed("(-1 + 2) * 3 - -4"), "(- (* (group (+ (- 1) 2)) 3) (- 4))");
+    assert_eq!(printed("10 - 2 - 3 / 4 / 5"), "(- (- 10 2) (/ (/ 3 4) 5))");
+    assert_eq!(printed("1 + 2.0 % 3 ~/ 4"), "(+ 1 (~/ (% 2.0 3) 4))");
+    assert_eq!(printed("-2 ** 3 ** -x * 2"), "(* (- (** 2 (** 3 (- x)))) 2)");
+    assert_eq!(printed("~a | b ^ c & d << 1 + 2"), "(| (~ a) (^ b (& c (<< d (+ 1 2)))))");
+    assert_eq!(printed(r#""a\tb" + """raw\n""""#), r#"(+ "a\tb" "raw\\n")"#);
+    assert_eq!(printed(r#""x = ${1 + 2}, y = ${"${3}"}!""#),
+               r#"(interpolate "x = " (+ 1 2) ", y = " (interpolate "" 3 "") "!")"#);
+    assert_eq!(printed("-f(1, g()(2)) * h"), "(* (- (call f 1 (call (call g) 2))) h)");
+    assert_eq!(printed("file.read(1).trim()"), "(invoke trim (invoke read file 1))");
+}
+
+#[test]
+fn records_spans() {
+    let expr = parse(String::from("1 +\n  (2 * 3)")).unwrap();
+
+    assert_eq!(expr.span, Span { start: 0, end: 13 });
+    assert_eq!(expr.line, 2);
+    match expr.kind {
+        ExprKind::Binary(BinaryOp::Add, left, right) => {
+            assert_eq!(left.span, Span { start: 0, end: 1 });
+            assert_eq!(left.line, 1);
+            assert_eq!(right.span, Span { start: 6, end: 13 });
+        }
+        kind => panic!("expected an addition, got {:?}", kind)
+    }
+}
+
+#[test]
+fn reports_errors() {
+    let errors = |source: &str| -> Vec<String> {
+        parse(String::from(source)).unwrap_err().iter().map(|error| error.to_string()).collect()
+    };
+
+    assert_eq!(errors("1 +"), ["[line 1] Error at end: Expect expression."]);
+    assert_eq!(errors("(1 + 2"), ["[line 1] Error at end: Expect ')' after expression."]);
+    assert_eq!(errors("1 2"), ["[line 1] Error at 2: Expect end of expression."]);
+    assert_eq!(errors("1e309"), ["[line 1] Error at 1e309: Number literal is too large."]);
+    assert_eq!(errors("\"a\\q\" + 1"), ["[line 1] Error: Invalid escape sequence"]);
+    assert_eq!(errors("f(1, 2"), ["[line 1] Error at end: Expect ')' after arguments."]);
+    assert_eq!(errors("f.x"), ["[line 1] Error at end: Expect '(' after method name."]);
+}
+
+#[test]
+fn generates_compiler_bytecode() {
+    let sources = [
+        "(-1 + 2) * 3 - -4",
+        "10 - 2 - 3 / 4 / 5",
+        "\"one\" +\n  \"two\"",
+        "\"sum: ${1 +\n 2} and ${\"${-3}\"}\"",
+        "0xFF * (1_000 - 2.5e1)",
+        "7 % x ~/ 2 + 6 / 4",
+        "-2 ** 3 ** -x * 2 ** 0.5",
+        "~a & 0xFF ^ b << 3 >> 1 | c + 1 & ~0",
+        "clock() - f(1 + 2,\n  \"${x}\")(y)",
+        "file.read(1 + 2).split(\"\",\n  \"${x}\")"
+    ];
+
+    for source in &sources {
+        let expected = Compiler::new(String::from(*source)).compile().unwrap();
+        let generated = generate(&parse(String::from(*source)).unwrap()).unwrap();
+        assert_eq!(generated, expected, "{}", source);
+    }
+}
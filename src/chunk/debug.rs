@@ -1,48 +1,92 @@
+use std::fmt::Write;
+
 use super::*;
-use crate::value;
 
 pub fn disassemble_chunk(chunk: &Chunk) {
+    print!("{}", disassemble(chunk));
+}
+
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
+    let mut out = String::new();
+    let next = instruction(chunk, offset, &mut out);
+    print!("{}", out);
+    next
+}
+
+/// Disassembles a whole chunk into text, one instruction per line.
+pub fn disassemble(chunk: &Chunk) -> String {
+    let mut out = String::new();
     let mut offset: usize = 0;
 
     while offset < chunk.code.len() {
-        offset = disassemble_instruction(chunk, offset);
+        offset = instruction(chunk, offset, &mut out);
     }
+    out
 }
 
-pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
-    print!("{:04} ", offset);
+fn instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    write!(out, "{:04} ", offset).unwrap();
     if offset > 0 &&
        chunk.lines[offset] == chunk.lines[offset-1]{
-        print!("   | ");
+        out.push_str("   | ");
     } else {
-        print!("{:4} ", chunk.lines[offset]);
+        write!(out, "{:4} ", chunk.lines[offset]).unwrap();
     }
 
     let instruction = &chunk.code[offset];
 
     match instruction {
-        OpCode::Constant => return constant_instruction("CONSTANT", chunk, offset),
-        OpCode::Negate => return simple_instruction("NEGATE", offset),
-        OpCode::Return => return simple_instruction("RETURN", offset),
-        OpCode::Add => return simple_instruction("ADD", offset),
-        OpCode::Subtract => return simple_instruction("SUBTRACT", offset),
-        OpCode::Multiply => return simple_instruction("MULTIPLY", offset),
-        OpCode::Divide => return simple_instruction("DIVIDE", offset),
-        _ => {}
+        OpCode::Constant => constant_instruction("CONSTANT", chunk, offset, out),
+        OpCode::Negate => simple_instruction("NEGATE", offset, out),
+        OpCode::Return => simple_instruction("RETURN", offset, out),
+        OpCode::Add => simple_instruction("ADD", offset, out),
+        OpCode::Subtract => simple_instruction("SUBTRACT", offset, out),
+        OpCode::Multiply => simple_instruction("MULTIPLY", offset, out),
+        OpCode::Divide => simple_instruction("DIVIDE", offset, out),
+        OpCode::Modulo => simple_instruction("MODULO", offset, out),
+        OpCode::IntDivide => simple_instruction("INT_DIVIDE", offset, out),
+        OpCode::Power => simple_instruction("POWER", offset, out),
+        OpCode::BitAnd => simple_instruction("BIT_AND", offset, out),
+        OpCode::BitOr => simple_instruction("BIT_OR", offset, out),
+        OpCode::BitXor => simple_instruction("BIT_XOR", offset, out),
+        OpCode::ShiftLeft => simple_instruction("SHIFT_LEFT", offset, out),
+        OpCode::ShiftRight => simple_instruction("SHIFT_RIGHT", offset, out),
+        OpCode::BitNot => simple_instruction("BIT_NOT", offset, out),
+        OpCode::BuildString => byte_instruction("BUILD_STRING", chunk, offset, out),
+        OpCode::GetGlobal => constant_instruction("GET_GLOBAL", chunk, offset, out),
+        OpCode::Call => byte_instruction("CALL", chunk, offset, out),
+        OpCode::Invoke => invoke_instruction("INVOKE", chunk, offset, out),
+        OpCode::OpArg(arg) => {
+            writeln!(out, "Unexpected argument {}", arg).unwrap();
+            offset + 1
+        }
     }
-    offset
 }
 
-fn simple_instruction(op_name: &str, offset: usize) -> usize {
-    println!("{}", op_name);
+fn simple_instruction(op_name: &str, offset: usize, out: &mut String) -> usize {
+    writeln!(out, "{}", op_name).unwrap();
     offset + 1
 }
 
-fn constant_instruction(op_name: &str, chunk: &Chunk, offset: usize) -> usize {
+fn byte_instruction(op_name: &str, chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    if let OpCode::OpArg(count) = &chunk.code[offset + 1] {
+        writeln!(out, "{:16} {:4}", op_name, count).unwrap();
+    }
+    offset + 2
+}
+
+fn invoke_instruction(op_name: &str, chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    if let (OpCode::OpArg(index), OpCode::OpArg(count)) = (&chunk.code[offset + 1], &chunk.code[offset + 2]) {
+        writeln!(out, "{:16} ({} args) {:4} '{}'", op_name, count, index,
+                 chunk.constants[*index]).unwrap();
+    }
+    offset + 3
+}
+
+fn constant_instruction(op_name: &str, chunk: &Chunk, offset: usize, out: &mut String) -> usize {
     if let OpCode::OpArg(index) = &chunk.code[offset + 1] {
-        print!("{:16} {:4} ", op_name, index);
-        value::print_value(chunk.constants[*index]);
-        println!();
+        writeln!(out, "{:16} {:4} '{}'", op_name, index,
+                 chunk.constants[*index]).unwrap();
     }
     offset + 2
 }
@@ -1,22 +1,35 @@
 use crate::value::Value;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum OpCode {
     Constant,
     Add,
     Subtract,
     Multiply,
     Divide,
+    Modulo,
+    IntDivide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     Negate,
+    BitNot,
+    BuildString,
+    GetGlobal,
+    Call,
+    Invoke,
     Return,
     OpArg(usize)
 }
 
-#[derive(Default)]
+#[derive(Clone, Default, Debug, PartialEq)]
 pub struct Chunk {
     pub code: Vec<OpCode>,
     pub constants: Vec<Value>,
-    lines: Vec<usize>
+    pub lines: Vec<usize>
 }
 
 impl Chunk {
@@ -29,6 +42,17 @@ impl Chunk {
         self.constants.push(value);
         self.constants.len() - 1
     }
+
+    /// Drops the instructions from `len` onwards, along with their lines.
+    pub fn truncate(&mut self, len: usize) {
+        self.code.truncate(len);
+        self.lines.truncate(len);
+    }
 }
 
 pub mod debug;
+pub mod serialize;
+pub mod verify;
+
+#[cfg(test)]
+mod test;
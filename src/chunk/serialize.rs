@@ -0,0 +1,196 @@
+use std::convert::TryInto;
+
+use super::*;
+use crate::value::type_name;
+
+/// First bytes of every `.loxc` file.
+pub const MAGIC: &[u8; 4] = b"LOXC";
+/// Bumped whenever the layout below or the instruction set changes.
+pub const VERSION: u16 = 6;
+
+const HEADER_LEN: usize = 10;
+
+/// Encodes a chunk as a `.loxc` file: the magic bytes, a little-endian u16
+/// version and a u32 FNV-1a checksum of the rest, followed by the code, the
+/// line table and the constants. Counts, operands and lines are u32s.
+/// Fails on constants holding host values, which only exist at runtime.
+pub fn serialize(chunk: &Chunk) -> Result<Vec<u8>, String> {
+    let mut payload = Vec::new();
+
+    write_u32(&mut payload, chunk.code.len());
+    for instruction in &chunk.code {
+        payload.push(tag(instruction));
+        if let OpCode::OpArg(arg) = instruction {
+            write_u32(&mut payload, *arg);
+        }
+    }
+
+    for line in &chunk.lines {
+        write_u32(&mut payload, *line);
+    }
+
+    write_u32(&mut payload, chunk.constants.len());
+    for (index, constant) in chunk.constants.iter().enumerate() {
+        match constant {
+            Value::Number(number) => {
+                payload.push(0);
+                payload.extend_from_slice(&number.to_le_bytes());
+            }
+            Value::String(string) => {
+                payload.push(1);
+                write_u32(&mut payload, string.len());
+                payload.extend_from_slice(string.as_bytes());
+            }
+            Value::Bool(boolean) => payload.extend_from_slice(&[2, *boolean as u8]),
+            Value::Nil => payload.push(3),
+            Value::Int(int) => {
+                payload.push(4);
+                payload.extend_from_slice(&int.to_le_bytes());
+            }
+            Value::Native(_) | Value::Userdata(_) => {
+                return Err(format!("Constant {} is {}, which cannot be saved.", index, type_name(constant)));
+            }
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Decodes a `.loxc` file. This only checks the encoding; the chunk still
+/// needs verifying before it can be trusted to run.
+pub fn deserialize(bytes: &[u8]) -> Result<Chunk, String> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(String::from("Not a compiled Lox file."));
+    }
+
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(format!("Unsupported bytecode version {}, expected {}.", version, VERSION));
+    }
+
+    let payload = &bytes[HEADER_LEN..];
+    if checksum(payload).to_le_bytes() != bytes[6..HEADER_LEN] {
+        return Err(String::from("Checksum mismatch, the file is corrupt."));
+    }
+
+    let mut reader = Reader { bytes: payload, offset: 0 };
+    let mut chunk: Chunk = Default::default();
+
+    let count = reader.u32()?;
+    for _ in 0..count {
+        let instruction = match reader.u8()? {
+            0 => OpCode::Constant,
+            1 => OpCode::Add,
+            2 => OpCode::Subtract,
+            3 => OpCode::Multiply,
+            4 => OpCode::Divide,
+            5 => OpCode::Negate,
+            6 => OpCode::BuildString,
+            7 => OpCode::Return,
+            8 => OpCode::OpArg(reader.u32()?),
+            9 => OpCode::GetGlobal,
+            10 => OpCode::Call,
+            11 => OpCode::Invoke,
+            12 => OpCode::Modulo,
+            13 => OpCode::IntDivide,
+            14 => OpCode::Power,
+            15 => OpCode::BitAnd,
+            16 => OpCode::BitOr,
+            17 => OpCode::BitXor,
+            18 => OpCode::ShiftLeft,
+            19 => OpCode::ShiftRight,
+            20 => OpCode::BitNot,
+            tag => return Err(format!("Unknown instruction tag {}.", tag))
+        };
+        chunk.code.push(instruction);
+    }
+
+    for _ in 0..count {
+        chunk.lines.push(reader.u32()?);
+    }
+
+    for _ in 0..reader.u32()? {
+        let constant = match reader.u8()? {
+            0 => Value::Number(f64::from_le_bytes(reader.take(8)?.try_into().unwrap())),
+            1 => {
+                let len = reader.u32()?;
+                let text = std::str::from_utf8(reader.take(len)?)
+                    .map_err(|_| String::from("String constant is not valid UTF-8."))?;
+                Value::String(text.into())
+            }
+            2 => Value::Bool(reader.u8()? != 0),
+            3 => Value::Nil,
+            4 => Value::Int(i64::from_le_bytes(reader.take(8)?.try_into().unwrap())),
+            tag => return Err(format!("Unknown constant tag {}.", tag))
+        };
+        chunk.constants.push(constant);
+    }
+
+    if reader.offset != payload.len() {
+        return Err(String::from("Unexpected bytes after the constants."));
+    }
+    Ok(chunk)
+}
+
+fn tag(instruction: &OpCode) -> u8 {
+    match instruction {
+        OpCode::Constant => 0,
+        OpCode::Add => 1,
+        OpCode::Subtract => 2,
+        OpCode::Multiply => 3,
+        OpCode::Divide => 4,
+        OpCode::Negate => 5,
+        OpCode::BuildString => 6,
+        OpCode::Return => 7,
+        OpCode::OpArg(_) => 8,
+        OpCode::GetGlobal => 9,
+        OpCode::Call => 10,
+        OpCode::Invoke => 11,
+        OpCode::Modulo => 12,
+        OpCode::IntDivide => 13,
+        OpCode::Power => 14,
+        OpCode::BitAnd => 15,
+        OpCode::BitOr => 16,
+        OpCode::BitXor => 17,
+        OpCode::ShiftLeft => 18,
+        OpCode::ShiftRight => 19,
+        OpCode::BitNot => 20
+    }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+// 32-bit FNV-1a.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self.offset.checked_add(len)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or_else(|| String::from("Unexpected end of file."))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+}
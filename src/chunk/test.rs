@@ -0,0 +1,103 @@
+use super::verify::verify;
+use super::*;
+use crate::compiler::Compiler;
+
+fn chunk(code: &[OpCode], constants: usize) -> Chunk {
+    Chunk {
+        code: code.to_vec(),
+        constants: (0..constants).map(|n| Value::Number(n as f64)).collect(),
+        lines: vec![1; code.len()]
+    }
+}
+
+fn rejected(chunk: &Chunk) -> String {
+    verify(chunk).unwrap_err().to_string()
+}
+
+#[test]
+fn accepts_compiled_chunks() {
+    for source in &["(-1 + 2) * 3 - -4", r#""${-"a"}" + "b" - 1"#, r#"-"x""#] {
+        let chunk = Compiler::new(String::from(*source)).compile().unwrap();
+        assert_eq!(verify(&chunk), Ok(()), "{}", source);
+    }
+}
+
+#[test]
+fn rejects_bad_operands() {
+    use OpCode::*;
+
+    assert_eq!(rejected(&chunk(&[Constant, OpArg(1), Return], 1)),
+               "Invalid chunk at offset 0000: Constant index 1 out of range.");
+    assert_eq!(rejected(&chunk(&[Constant, Return], 1)),
+               "Invalid chunk at offset 0000: Missing operand.");
+    assert_eq!(rejected(&chunk(&[Constant, OpArg(0), OpArg(0), Return], 1)),
+               "Invalid chunk at offset 0002: Operand in place of an instruction.");
+}
+
+#[test]
+fn rejects_unbalanced_stacks() {
+    use OpCode::*;
+
+    assert_eq!(rejected(&chunk(&[Constant, OpArg(0), Add, Return], 1)),
+               "Invalid chunk at offset 0002: Stack underflow.");
+    assert_eq!(rejected(&chunk(&[BuildString, OpArg(2), Return], 0)),
+               "Invalid chunk at offset 0000: Stack underflow.");
+    assert_eq!(rejected(&chunk(&[Constant, OpArg(0), Constant, OpArg(0), Return], 1)),
+               "Invalid chunk at offset 0004: Return with 2 values on the stack.");
+}
+
+#[test]
+fn rejects_bad_endings() {
+    use OpCode::*;
+
+    assert_eq!(rejected(&chunk(&[Constant, OpArg(0)], 1)),
+               "Invalid chunk at offset 0002: Chunk does not end with a return.");
+    assert_eq!(rejected(&chunk(&[Constant, OpArg(0), Return, Negate], 1)),
+               "Invalid chunk at offset 0003: Unreachable code after return.");
+
+    let mut short = chunk(&[Constant, OpArg(0), Return], 1);
+    short.lines.pop();
+    assert_eq!(rejected(&short), "Invalid chunk at offset 0000: Line table has 2 entries for 3 instructions.");
+}
+
+#[test]
+fn serializes_round_trip() {
+    use super::serialize::*;
+
+    let source = "\"π = ${3.14159}\" + \"\"\"raw\"\"\" + \"${-\"a\"}\"\n- 1";
+    let chunk = Compiler::new(String::from(source)).compile().unwrap();
+    let bytes = serialize(&chunk).unwrap();
+
+    assert_eq!(&bytes[..4], MAGIC);
+    assert_eq!(deserialize(&bytes), Ok(chunk));
+}
+
+#[test]
+fn refuses_host_value_constants() {
+    use super::serialize::*;
+    use crate::value::userdata::*;
+    use OpCode::*;
+    use std::rc::Rc;
+
+    let mut chunk = chunk(&[Constant, OpArg(1), Return], 2);
+    chunk.constants[1] = new_userdata(&Rc::new(UserClass::new("Counter")), 0_i64);
+
+    assert_eq!(serialize(&chunk), Err(String::from("Constant 1 is an object, which cannot be saved.")));
+}
+
+#[test]
+fn rejects_damaged_files() {
+    use super::serialize::*;
+
+    let bytes = serialize(&Compiler::new(String::from("1 + \"a\"")).compile().unwrap()).unwrap();
+
+    assert_eq!(deserialize(b"print 1;"), Err(String::from("Not a compiled Lox file.")));
+
+    let mut future = bytes.clone();
+    future[4] = 99;
+    assert_eq!(deserialize(&future), Err(String::from("Unsupported bytecode version 99, expected 6.")));
+
+    let mut corrupt = bytes.clone();
+    *corrupt.last_mut().unwrap() ^= 1;
+    assert_eq!(deserialize(&corrupt), Err(String::from("Checksum mismatch, the file is corrupt.")));
+}
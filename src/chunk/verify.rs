@@ -0,0 +1,96 @@
+use std::fmt;
+
+use super::*;
+
+/// Why a chunk was rejected, and the offset of the instruction at fault.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct VerifyError {
+    pub offset: usize,
+    pub message: String
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid chunk at offset {:04}: {}", self.offset, self.message)
+    }
+}
+
+/// Checks that a chunk from an untrusted source is safe to run: every
+/// instruction decodes, every operand is in range, the stack never runs
+/// dry and the chunk ends by returning the single value it computed.
+///
+/// Chunks hold neither jumps nor locals yet, so the code is one straight
+/// path and a single walk over it sees every stack depth.
+pub fn verify(chunk: &Chunk) -> Result<(), VerifyError> {
+    if chunk.lines.len() != chunk.code.len() {
+        return Err(error(0, format!("Line table has {} entries for {} instructions.",
+                                    chunk.lines.len(), chunk.code.len())));
+    }
+
+    let mut depth: usize = 0;
+    let mut offset = 0;
+
+    while offset < chunk.code.len() {
+        let (popped, pushed, size) = match chunk.code[offset] {
+            OpCode::Constant => {
+                let index = operand(chunk, offset)?;
+                if index >= chunk.constants.len() {
+                    return Err(error(offset, format!("Constant index {} out of range.", index)));
+                }
+                (0, 1, 2)
+            }
+            OpCode::GetGlobal => {
+                name(chunk, offset, operand(chunk, offset)?)?;
+                (0, 1, 2)
+            }
+            OpCode::Invoke => {
+                name(chunk, offset, operand(chunk, offset)?)?;
+                (operand(chunk, offset + 1)? + 1, 1, 3)
+            }
+            OpCode::BuildString => (operand(chunk, offset)?, 1, 2),
+            OpCode::Call => (operand(chunk, offset)? + 1, 1, 2),
+            OpCode::Negate | OpCode::BitNot => (1, 1, 1),
+            OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide |
+            OpCode::Modulo | OpCode::IntDivide | OpCode::Power |
+            OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor |
+            OpCode::ShiftLeft | OpCode::ShiftRight => (2, 1, 1),
+            OpCode::Return => {
+                if depth != 1 {
+                    return Err(error(offset, format!("Return with {} values on the stack.", depth)));
+                }
+                if offset + 1 != chunk.code.len() {
+                    return Err(error(offset + 1, String::from("Unreachable code after return.")));
+                }
+                return Ok(());
+            }
+            OpCode::OpArg(_) => return Err(error(offset, String::from("Operand in place of an instruction.")))
+        };
+
+        if popped > depth {
+            return Err(error(offset, String::from("Stack underflow.")));
+        }
+        depth = depth - popped + pushed;
+        offset += size;
+    }
+
+    Err(error(chunk.code.len(), String::from("Chunk does not end with a return.")))
+}
+
+fn operand(chunk: &Chunk, offset: usize) -> Result<usize, VerifyError> {
+    match chunk.code.get(offset + 1) {
+        Some(OpCode::OpArg(arg)) => Ok(*arg),
+        _ => Err(error(offset, String::from("Missing operand.")))
+    }
+}
+
+fn name(chunk: &Chunk, offset: usize, index: usize) -> Result<(), VerifyError> {
+    match chunk.constants.get(index) {
+        Some(Value::String(_)) => Ok(()),
+        _ => Err(error(offset, format!("Constant {} is not a name.", index)))
+    }
+}
+
+fn error(offset: usize, message: String) -> VerifyError {
+    VerifyError { offset, message }
+}
@@ -0,0 +1,54 @@
+use std::fmt;
+
+use crate::scanner::*;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Copy, Clone)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+/// A message about a place in the source, shared by compile errors and
+/// lint warnings. Displays as `[line 3] Error at x: message`.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub location: Option<String>,
+    pub message: String
+}
+
+impl Diagnostic {
+    pub fn at(severity: Severity, token: &Token, message: String) -> Self {
+        let location = match token.token_type {
+            TokenType::EOF => Some(String::from("end")),
+            TokenType::Error => None,
+            _ => Some(token.lexeme.clone())
+        };
+
+        Self {
+            severity,
+            line: token.line,
+            location,
+            message
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "Error",
+            Severity::Warning => "Warning"
+        };
+        write!(f, "[line {}] {}", self.line, severity)?;
+
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::chunk::*;
+use crate::value::*;
+
+/// Writes an operator instruction, or, when all of its operands were just
+/// pushed as constants, replaces them with one constant holding the result.
+/// Operations that raise an error at runtime are left for the VM to raise.
+///
+/// This relies on no jump landing between an operand and its operator,
+/// which holds as long as expressions have no control flow.
+pub(crate) fn write_operator(chunk: &mut Chunk, instruction: OpCode, line: usize) {
+    let operands = match instruction {
+        OpCode::Negate | OpCode::BitNot => 1,
+        OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide |
+        OpCode::Modulo | OpCode::IntDivide | OpCode::Power |
+        OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor |
+        OpCode::ShiftLeft | OpCode::ShiftRight => 2,
+        _ => 0
+    };
+
+    let indices = match trailing_constants(chunk, operands) {
+        Some(indices) if operands > 0 => indices,
+        _ => return chunk.write_chunk(instruction, line)
+    };
+
+    match fold(chunk, instruction, &indices) {
+        Some(value) => replace_constants(chunk, &indices, value, line),
+        None => chunk.write_chunk(instruction, line)
+    }
+}
+
+/// Writes a BuildString instruction joining the last `count` values, folding
+/// it like any other operator when every part is a constant.
+pub(crate) fn write_build_string(chunk: &mut Chunk, count: usize, line: usize) {
+    let parts = match trailing_constants(chunk, count) {
+        Some(parts) => parts,
+        None => {
+            chunk.write_chunk(OpCode::BuildString, line);
+            chunk.write_chunk(OpCode::OpArg(count), line);
+            return;
+        }
+    };
+
+    let joined: String = parts.iter().map(|&index| chunk.constants[index].to_string()).collect();
+    replace_constants(chunk, &parts, Value::String(joined.into()), line);
+}
+
+fn fold(chunk: &Chunk, instruction: OpCode, indices: &[usize]) -> Option<Value> {
+    let operand = |n: usize| &chunk.constants[indices[n]];
+
+    let result = match instruction {
+        OpCode::Negate => negate(operand(0)),
+        OpCode::BitNot => complement(operand(0)),
+        OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor |
+        OpCode::ShiftLeft | OpCode::ShiftRight => bitwise(instruction, operand(0), operand(1)),
+        _ => arithmetic(instruction, operand(0), operand(1))
+    };
+    result.ok()
+}
+
+// Constant indices of the last `count` instructions, oldest first, if each
+// of them is a Constant.
+fn trailing_constants(chunk: &Chunk, count: usize) -> Option<Vec<usize>> {
+    let start = chunk.code.len().checked_sub(count * 2)?;
+
+    chunk.code[start..].chunks(2).map(|instruction| {
+        match instruction {
+            [OpCode::Constant, OpCode::OpArg(index)] => Some(*index),
+            _ => None
+        }
+    }).collect()
+}
+
+// Replaces the Constant instructions that pushed `indices` with one pushing
+// `value`. Their constants go too when they are the last in the table.
+fn replace_constants(chunk: &mut Chunk, indices: &[usize], value: Value, line: usize) {
+    chunk.truncate(chunk.code.len() - indices.len() * 2);
+
+    let first = chunk.constants.len() - indices.len().min(chunk.constants.len());
+    if indices.iter().copied().eq(first..chunk.constants.len()) {
+        chunk.constants.truncate(first);
+    }
+
+    let index = chunk.add_constant(value);
+    chunk.write_chunk(OpCode::Constant, line);
+    chunk.write_chunk(OpCode::OpArg(index), line);
+}
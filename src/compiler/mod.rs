@@ -1,50 +1,70 @@
 use std::error::Error;
+use std::num::IntErrorKind;
 
 use crate::chunk::*;
 use crate::scanner::*;
 use crate::value::*;
+use diagnostic::*;
+use fold::*;
 
 #[derive(PartialOrd, PartialEq)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
 enum Precedence {
-    None = 0,
-    Assignment = 1,
-    Or = 2,
-    And = 3,
-    Equality = 4,
-    Comparision = 5,
-    Term = 6,
-    Factor = 7,
-    Unary = 8,
-    Call = 9,
-    Primary = 10
-}
-
-impl Default for Precedence {
-    fn default() -> Precedence {
-        Precedence::None
-    }
+    #[default]
+    None,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparision,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Term,
+    Factor,
+    Unary,
+    Power,
+    Call,
+    Primary
 }
 
 impl Precedence {
-    fn from_u8(num: u8) -> Self {
-        match num {
-            0 => Precedence::None,
-            1 => Precedence::Assignment,
-            2 => Precedence::Or,
-            3 => Precedence::And,
-            4 => Precedence::Equality,
-            5 => Precedence::Comparision,
-            6 => Precedence::Term,
-            7 => Precedence::Factor,
-            8 => Precedence::Unary,
-            9 => Precedence::Call,
-            _ => Precedence::Primary,
+    // The level just above this one, which the right operand of a
+    // left-associative operator is parsed at.
+    fn next(self) -> Self {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparision,
+            Precedence::Comparision => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Power,
+            Precedence::Power => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary
         }
     }
 }
 
-type ParseFn =  Option<Box<Fn(&mut Compiler)>>;
+/// How much work the compiler puts into shrinking the code it emits.
+#[derive(Debug, PartialEq)]
+#[derive(Copy, Clone, Default)]
+pub enum OptLevel {
+    /// Emit code exactly as the source spells it.
+    None,
+    /// Fold constant operators and run the peephole pass.
+    #[default]
+    Basic
+}
+
+type ParseFn =  Option<Box<dyn Fn(&mut Compiler)>>;
 
 #[derive(Default)]
 pub struct ParseRule(
@@ -61,22 +81,36 @@ pub struct Compiler {
     panic_mode: bool,
     scanner: Scanner,
     chunk: Chunk,
-    current_precedence: Precedence
+    current_precedence: Precedence,
+    opt_level: OptLevel
 }
 
 pub struct ParseRules {
-    rules: [ParseRule; 32]
+    rules: Vec<ParseRule>
 }
 
 impl Default for ParseRules {
     fn default() -> ParseRules {
-        let mut rules:[ParseRule; 32] = Default::default();
+        let mut rules: Vec<ParseRule> = (0..=TokenType::EOF as usize).map(|_| Default::default()).collect();
         rules[TokenType::Plus as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Term);
         rules[TokenType::Minus as usize] = ParseRule(Some(Box::new(unary)), Some(Box::new(binary)), Precedence::Term);
         rules[TokenType::Star as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Factor);
         rules[TokenType::Slash as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Factor);
-        rules[TokenType::Number as usize] = ParseRule(Some(Box::new(number)), None, Precedence::Term);
-        rules[TokenType::LeftParen as usize] = ParseRule(Some(Box::new(grouping)), None, Precedence::Term);
+        rules[TokenType::Percent as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Factor);
+        rules[TokenType::TildeSlash as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Factor);
+        rules[TokenType::StarStar as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Power);
+        rules[TokenType::Ampersand as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::BitAnd);
+        rules[TokenType::Pipe as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::BitOr);
+        rules[TokenType::Caret as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::BitXor);
+        rules[TokenType::LessLess as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Shift);
+        rules[TokenType::GreaterGreater as usize] = ParseRule(None, Some(Box::new(binary)), Precedence::Shift);
+        rules[TokenType::Tilde as usize] = ParseRule(Some(Box::new(unary)), None, Precedence::None);
+        rules[TokenType::Number as usize] = ParseRule(Some(Box::new(number)), None, Precedence::None);
+        rules[TokenType::String as usize] = ParseRule(Some(Box::new(string)), None, Precedence::None);
+        rules[TokenType::Interpolation as usize] = ParseRule(Some(Box::new(interpolation)), None, Precedence::None);
+        rules[TokenType::LeftParen as usize] = ParseRule(Some(Box::new(grouping)), Some(Box::new(call)), Precedence::Call);
+        rules[TokenType::Dot as usize] = ParseRule(None, Some(Box::new(dot)), Precedence::Call);
+        rules[TokenType::Identifier as usize] = ParseRule(Some(Box::new(variable)), None, Precedence::None);
 
         ParseRules {
             rules
@@ -92,20 +126,19 @@ impl Compiler {
         }
     }
 
+    pub fn with_opt_level(source: String, opt_level: OptLevel) -> Self {
+        Self {
+            opt_level,
+            ..Compiler::new(source)
+        }
+    }
+
     fn error_at(&self, token: &Token, message: String) {
         if self.panic_mode {
             return
         }
 
-        eprint!("[line {}] Error", token.line);
-
-        if token.token_type == TokenType::EOF {
-            eprint!(" at end");
-        } else {
-            eprint!(" at {}", token.lexeme);
-        }
-
-        eprintln!(": {}", message);
+        eprintln!("{}", Diagnostic::at(Severity::Error, token, message));
     }
 
     fn error_at_current(&mut self, message: String) {
@@ -121,21 +154,16 @@ impl Compiler {
     }
 
     fn advance(&mut self) {
-        loop {
-            self.previous = std::mem::take(&mut self.current);
-            let token = self.scanner.next();
-            
-            match token {
-                Some(token) => {
-                    self.current = token;
-                    if self.current.token_type != TokenType::Error {
-                        break;
-                    }
-                    self.error_at_current(String::from(&self.current.lexeme));
-                },
-                None => break
+        self.previous = std::mem::take(&mut self.current);
+
+        while let Some(token) = self.scanner.next() {
+            self.current = token;
+            if self.current.token_type != TokenType::Error {
+                return;
             }
+            self.error_at_current(String::from(&self.current.lexeme));
         }
+        self.current = self.scanner.eof();
     }
 
     fn consume(&mut self, token_type: TokenType, message: String) {
@@ -146,6 +174,14 @@ impl Compiler {
         self.error_at_current(message);
     }
 
+    fn matches(&mut self, token_type: TokenType) -> bool {
+        if self.current.token_type != token_type {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
     fn emit_byte(&mut self, byte: OpCode) {
         self.chunk.write_chunk(byte, self.previous.line);
     }
@@ -155,6 +191,13 @@ impl Compiler {
         self.emit_byte(byte2);
     }
 
+    fn emit_operator(&mut self, operator: OpCode) {
+        match self.opt_level {
+            OptLevel::None => self.emit_byte(operator),
+            OptLevel::Basic => write_operator(&mut self.chunk, operator, self.previous.line)
+        }
+    }
+
     fn end_compiler(&mut self) {
         self.emit_return();
     }
@@ -180,7 +223,17 @@ impl Compiler {
     pub fn compile(&mut self) -> Result<Chunk, Box<dyn Error>> {
         self.advance();
         expression(self);
+        if self.current.token_type != TokenType::EOF {
+            self.error_at_current(String::from("Expect end of expression."));
+        }
         self.end_compiler();
+
+        if self.had_error {
+            return Err("Compilation failed.".into());
+        }
+        if self.opt_level == OptLevel::Basic {
+            peephole::optimize(&mut self.chunk);
+        }
         Ok(std::mem::take(&mut self.chunk))
     }
 }
@@ -190,7 +243,13 @@ fn parse_precedence(compiler: &mut Compiler, precedence: Precedence) {
     let rules = parse_rules.rules;
     compiler.advance();
 
-    let prefix = rules[compiler.previous.token_type as usize].0.as_ref().unwrap();
+    let prefix = match rules[compiler.previous.token_type as usize].0.as_ref() {
+        Some(prefix) => prefix,
+        None => {
+            compiler.error(String::from("Expect expression."));
+            return;
+        }
+    };
     prefix(compiler);
 
     while precedence <= rules[compiler.current.token_type as usize].2 {
@@ -204,8 +263,95 @@ fn parse_precedence(compiler: &mut Compiler, precedence: Precedence) {
 }
 
 fn number(compiler: &mut Compiler) {
-    let value = compiler.previous.lexeme.parse::<Value>().unwrap();
-    compiler.emit_constant(value);
+    match parse_number(&compiler.previous.lexeme) {
+        Ok(value) => compiler.emit_constant(value),
+        Err(message) => compiler.error(message)
+    }
+}
+
+// Literals without a fraction or exponent are integers; the rest are floats.
+// Hex and binary literals spell out all 64 bits, so `0xFFFF_FFFF_FFFF_FFFF`
+// is -1, while decimal integers too large for 64 bits become floats.
+pub(crate) fn parse_number(lexeme: &str) -> Result<Value, String> {
+    let digits = lexeme.replace('_', "");
+
+    match digits.get(..2) {
+        Some("0x") | Some("0X") => return parse_bits(&digits[2..], 16),
+        Some("0b") | Some("0B") => return parse_bits(&digits[2..], 2),
+        _ => {}
+    }
+    if !digits.contains(['.', 'e', 'E']) {
+        if let Ok(value) = digits.parse::<i64>() {
+            return Ok(Value::Int(value));
+        }
+    }
+
+    let value = digits.parse::<f64>().map_err(|_| String::from("Invalid number literal."))?;
+    if !value.is_finite() {
+        return Err(String::from("Number literal is too large."));
+    }
+    Ok(Value::Number(value))
+}
+
+fn parse_bits(digits: &str, radix: u32) -> Result<Value, String> {
+    match u64::from_str_radix(digits, radix) {
+        Ok(value) => Ok(Value::Int(value as i64)),
+        Err(e) if *e.kind() == IntErrorKind::PosOverflow => Err(String::from("Number literal is too large.")),
+        Err(_) => Err(String::from("Invalid number literal."))
+    }
+}
+
+fn string(compiler: &mut Compiler) {
+    let lexeme = &compiler.previous.lexeme;
+
+    let value = if lexeme.starts_with("\"\"\"") {
+        Ok(String::from(&lexeme[3..lexeme.len() - 3]))
+    } else {
+        unescape(&lexeme[1..lexeme.len() - 1])
+    };
+    string_constant(compiler, value);
+}
+
+// Each part is emitted as a constant, followed by the expression that comes
+// after it, and BuildString joins them all once the closing part is reached.
+fn interpolation(compiler: &mut Compiler) {
+    let mut count = 0;
+
+    loop {
+        let lexeme = &compiler.previous.lexeme;
+        let value = unescape(&lexeme[1..lexeme.len() - 2]);
+        string_constant(compiler, value);
+        expression(compiler);
+        count += 2;
+
+        if !compiler.matches(TokenType::Interpolation) {
+            break;
+        }
+    }
+
+    // The closing part is a String token that picks up at the `}`.
+    if compiler.current.token_type != TokenType::String || !compiler.current.lexeme.starts_with('}') {
+        compiler.error_at_current(String::from("Expect end of string interpolation."));
+        return;
+    }
+    compiler.advance();
+
+    let lexeme = &compiler.previous.lexeme;
+    let value = unescape(&lexeme[1..lexeme.len() - 1]);
+    string_constant(compiler, value);
+    count += 1;
+
+    match compiler.opt_level {
+        OptLevel::None => compiler.emit_bytes(OpCode::BuildString, OpCode::OpArg(count)),
+        OptLevel::Basic => write_build_string(&mut compiler.chunk, count, compiler.previous.line)
+    }
+}
+
+fn string_constant(compiler: &mut Compiler, value: Result<String, String>) {
+    match value {
+        Ok(value) => compiler.emit_constant(Value::String(value.into())),
+        Err(message) => compiler.error(message)
+    }
 }
 
 fn expression(compiler: &mut Compiler) {
@@ -218,7 +364,8 @@ fn unary(compiler: &mut Compiler) {
     parse_precedence(compiler, Precedence::Unary);
 
     match operator {
-        TokenType::Minus => compiler.emit_byte(OpCode::Negate),
+        TokenType::Minus => compiler.emit_operator(OpCode::Negate),
+        TokenType::Tilde => compiler.emit_operator(OpCode::BitNot),
         _ => {}
     }
 }
@@ -226,20 +373,84 @@ fn unary(compiler: &mut Compiler) {
 fn binary(compiler: &mut Compiler) {
     let operator = compiler.previous.token_type;
 
-    parse_precedence(compiler, Precedence::from_u8(compiler.current_precedence as u8));
+    // `**` groups to the right and lets its exponent start with a sign,
+    // as in `2 ** -1`.
+    if operator == TokenType::StarStar {
+        parse_precedence(compiler, Precedence::Unary);
+    } else {
+        parse_precedence(compiler, compiler.current_precedence.next());
+    }
     
     match operator {
-        TokenType::Plus => compiler.emit_byte(OpCode::Add),
-        TokenType::Minus => compiler.emit_byte(OpCode::Subtract),
-        TokenType::Star => compiler.emit_byte(OpCode::Multiply),
-        TokenType::Slash => compiler.emit_byte(OpCode::Divide),
+        TokenType::Plus => compiler.emit_operator(OpCode::Add),
+        TokenType::Minus => compiler.emit_operator(OpCode::Subtract),
+        TokenType::Star => compiler.emit_operator(OpCode::Multiply),
+        TokenType::Slash => compiler.emit_operator(OpCode::Divide),
+        TokenType::Percent => compiler.emit_operator(OpCode::Modulo),
+        TokenType::TildeSlash => compiler.emit_operator(OpCode::IntDivide),
+        TokenType::StarStar => compiler.emit_operator(OpCode::Power),
+        TokenType::Ampersand => compiler.emit_operator(OpCode::BitAnd),
+        TokenType::Pipe => compiler.emit_operator(OpCode::BitOr),
+        TokenType::Caret => compiler.emit_operator(OpCode::BitXor),
+        TokenType::LessLess => compiler.emit_operator(OpCode::ShiftLeft),
+        TokenType::GreaterGreater => compiler.emit_operator(OpCode::ShiftRight),
         _ => {}
     }
 }
 
 
+fn variable(compiler: &mut Compiler) {
+    let name = compiler.previous.lexeme.clone();
+    let index = compiler.make_constant(Value::String(name.into()));
+    compiler.emit_bytes(OpCode::GetGlobal, OpCode::OpArg(index));
+}
+
+fn call(compiler: &mut Compiler) {
+    let count = argument_list(compiler);
+    compiler.emit_bytes(OpCode::Call, OpCode::OpArg(count));
+}
+
+// Only method calls are supported after a dot, as the only values with
+// members are host objects, which have methods but no fields.
+fn dot(compiler: &mut Compiler) {
+    compiler.consume(TokenType::Identifier, String::from("Expect property name after '.'."));
+    let name = compiler.previous.lexeme.clone();
+    let index = compiler.make_constant(Value::String(name.into()));
+
+    compiler.consume(TokenType::LeftParen, String::from("Expect '(' after method name."));
+    let count = argument_list(compiler);
+    compiler.emit_bytes(OpCode::Invoke, OpCode::OpArg(index));
+    compiler.emit_byte(OpCode::OpArg(count));
+}
+
+fn argument_list(compiler: &mut Compiler) -> usize {
+    let mut count = 0;
+
+    if compiler.current.token_type != TokenType::RightParen {
+        loop {
+            expression(compiler);
+            if count == 255 {
+                compiler.error(String::from("Can't have more than 255 arguments."));
+            }
+            count += 1;
+
+            if !compiler.matches(TokenType::Comma) {
+                break;
+            }
+        }
+    }
+    compiler.consume(TokenType::RightParen, String::from("Expect ')' after arguments."));
+    count
+}
+
 fn grouping(compiler: &mut Compiler) {
     expression(compiler);
     compiler.consume(TokenType::RightParen, String::from("Expect ')' after expression."));
 }
 
+pub mod diagnostic;
+pub(crate) mod fold;
+pub mod peephole;
+
+#[cfg(test)]
+mod test;
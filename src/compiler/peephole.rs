@@ -0,0 +1,147 @@
+use crate::chunk::*;
+use crate::value::*;
+
+/// Rewrites a compiled chunk in place into equivalent, shorter code,
+/// keeping the line table in step with the instructions.
+///
+/// Chunks hold no jumps yet, so instructions can be dropped without
+/// patching any offsets.
+pub fn optimize(chunk: &mut Chunk) {
+    drop_empty_parts(chunk);
+    drop_unused_constants(chunk);
+}
+
+// A value on the simulated stack: the offset of the first instruction that
+// went into it, and its constant when a lone Constant pushed it.
+struct Slot {
+    start: usize,
+    constant: Option<usize>
+}
+
+// Removes the empty literal parts that string interpolation leaves around
+// its expressions, such as both ends of "${x}", and lowers the count of the
+// BuildString joining them.
+fn drop_empty_parts(chunk: &mut Chunk) {
+    let mut removed = vec![false; chunk.code.len()];
+    let mut stack: Vec<Slot> = Vec::new();
+    let mut offset = 0;
+
+    while offset < chunk.code.len() {
+        let popped = match (chunk.code[offset], chunk.code.get(offset + 1)) {
+            (OpCode::Constant, Some(&OpCode::OpArg(index))) => {
+                stack.push(Slot { start: offset, constant: Some(index) });
+                offset += 2;
+                continue;
+            }
+            (OpCode::GetGlobal, Some(&OpCode::OpArg(_))) => {
+                stack.push(Slot { start: offset, constant: None });
+                offset += 2;
+                continue;
+            }
+            (OpCode::Call, Some(&OpCode::OpArg(count))) => {
+                if !call(&mut stack, count) {
+                    return;
+                }
+                offset += 2;
+                continue;
+            }
+            (OpCode::Invoke, Some(&OpCode::OpArg(_))) => {
+                match chunk.code.get(offset + 2) {
+                    Some(&OpCode::OpArg(count)) if call(&mut stack, count) => {}
+                    _ => return
+                }
+                offset += 3;
+                continue;
+            }
+            (OpCode::Negate, _) | (OpCode::BitNot, _) | (OpCode::Return, _) => 1,
+            (OpCode::Add, _) | (OpCode::Subtract, _) |
+            (OpCode::Multiply, _) | (OpCode::Divide, _) |
+            (OpCode::Modulo, _) | (OpCode::IntDivide, _) | (OpCode::Power, _) |
+            (OpCode::BitAnd, _) | (OpCode::BitOr, _) | (OpCode::BitXor, _) |
+            (OpCode::ShiftLeft, _) | (OpCode::ShiftRight, _) => 2,
+            (OpCode::BuildString, Some(&OpCode::OpArg(count))) => {
+                if count > stack.len() {
+                    return;
+                }
+                let parts = stack.split_off(stack.len() - count);
+                let mut kept = count;
+
+                for part in &parts {
+                    if part.constant.is_some_and(|index| is_empty_string(&chunk.constants[index])) {
+                        removed[part.start] = true;
+                        removed[part.start + 1] = true;
+                        kept -= 1;
+                    }
+                }
+                chunk.code[offset + 1] = OpCode::OpArg(kept);
+
+                let start = parts.first().map_or(offset, |part| part.start);
+                stack.push(Slot { start, constant: None });
+                offset += 2;
+                continue;
+            }
+            // Code this pass does not understand is left as it is.
+            _ => return
+        };
+
+        if popped > stack.len() {
+            return;
+        }
+        let start = stack.split_off(stack.len() - popped)[0].start;
+        if chunk.code[offset] != OpCode::Return {
+            stack.push(Slot { start, constant: None });
+        }
+        offset += 1;
+    }
+
+    let mut index = 0;
+    chunk.code.retain(|_| { index += 1; !removed[index - 1] });
+    let mut index = 0;
+    chunk.lines.retain(|_| { index += 1; !removed[index - 1] });
+}
+
+// Replaces a callee or receiver and its arguments with the call's result.
+fn call(stack: &mut Vec<Slot>, count: usize) -> bool {
+    if count >= stack.len() {
+        return false;
+    }
+    let start = stack.split_off(stack.len() - count - 1)[0].start;
+    stack.push(Slot { start, constant: None });
+    true
+}
+
+fn is_empty_string(value: &Value) -> bool {
+    matches!(value, Value::String(string) if string.is_empty())
+}
+
+// Compacts the constant table down to the constants still referenced,
+// renumbering the instructions that refer to them.
+fn drop_unused_constants(chunk: &mut Chunk) {
+    let mut used = vec![false; chunk.constants.len()];
+    for pair in chunk.code.windows(2) {
+        if let [OpCode::Constant, OpCode::OpArg(index)] | [OpCode::GetGlobal, OpCode::OpArg(index)] |
+               [OpCode::Invoke, OpCode::OpArg(index)] = pair {
+            used[*index] = true;
+        }
+    }
+
+    let mut renumbered = Vec::with_capacity(used.len());
+    let mut next = 0;
+    for &used in &used {
+        renumbered.push(next);
+        if used {
+            next += 1;
+        }
+    }
+
+    for offset in 1..chunk.code.len() {
+        if let (OpCode::Constant, OpCode::OpArg(index)) |
+               (OpCode::GetGlobal, OpCode::OpArg(index)) |
+               (OpCode::Invoke, OpCode::OpArg(index)) = (chunk.code[offset - 1], chunk.code[offset]) {
+            chunk.code[offset] = OpCode::OpArg(renumbered[index]);
+        }
+    }
+
+    let mut index = 0;
+    chunk.constants.retain(|_| { index += 1; used[index - 1] });
+}
@@ -0,0 +1,129 @@
+use super::*;
+use crate::chunk::debug::disassemble;
+
+fn disassembled(source: &str) -> String {
+    disassemble(&Compiler::new(String::from(source)).compile().unwrap())
+}
+
+#[test]
+fn folds_arithmetic() {
+    assert_eq!(disassembled("(-1 + 2) * 3 - -4"),
+               "0000    1 CONSTANT            0 '7'\n\
+                0002    | RETURN\n");
+    assert_eq!(disassembled("1 +\n2 * 3"),
+               "0000    2 CONSTANT            0 '7'\n\
+                0002    | RETURN\n");
+}
+
+#[test]
+fn folds_division_by_zero() {
+    assert_eq!(disassembled("1 / 0"),
+               "0000    1 CONSTANT            0 'inf'\n\
+                0002    | RETURN\n");
+}
+
+#[test]
+fn folds_string_concatenation() {
+    assert_eq!(disassembled(r#""a" + "b" + "c""#),
+               "0000    1 CONSTANT            0 'abc'\n\
+                0002    | RETURN\n");
+    assert_eq!(disassembled(r#""x = ${1 + 2}!""#),
+               "0000    1 CONSTANT            0 'x = 3!'\n\
+                0002    | RETURN\n");
+}
+
+#[test]
+fn folds_integer_operators() {
+    assert_eq!(disassembled("-7 ~/ 2 + 7 % 3"),
+               "0000    1 CONSTANT            0 '-3'\n\
+                0002    | RETURN\n");
+    assert_eq!(disassembled("7 / 2 + 0.5"),
+               "0000    1 CONSTANT            0 '4.0'\n\
+                0002    | RETURN\n");
+}
+
+#[test]
+fn folds_power_and_bitwise_operators() {
+    assert_eq!(disassembled("-2 ** 2 + (0xF0 | 0x0F) ^ ~0 << 8"),
+               "0000    1 CONSTANT            0 '-5'\n\
+                0002    | RETURN\n");
+    assert_eq!(disassembled("x ** 2 & 1"),
+               "0000    1 GET_GLOBAL          0 'x'\n\
+                0002    | CONSTANT            1 '2'\n\
+                0004    | POWER\n\
+                0005    | CONSTANT            2 '1'\n\
+                0007    | BIT_AND\n\
+                0008    | RETURN\n");
+    assert_eq!(disassembled("1.5 & 1"),
+               "0000    1 CONSTANT            0 '1.5'\n\
+                0002    | CONSTANT            1 '1'\n\
+                0004    | BIT_AND\n\
+                0005    | RETURN\n");
+}
+
+#[test]
+fn leaves_runtime_errors_to_the_vm() {
+    assert_eq!(disassembled(r#"-"a" + 1"#),
+               "0000    1 CONSTANT            0 'a'\n\
+                0002    | NEGATE\n\
+                0003    | CONSTANT            1 '1'\n\
+                0005    | ADD\n\
+                0006    | RETURN\n");
+    assert_eq!(disassembled(r#"2 * 3 - "b""#),
+               "0000    1 CONSTANT            0 '6'\n\
+                0002    | CONSTANT            1 'b'\n\
+                0004    | SUBTRACT\n\
+                0005    | RETURN\n");
+    assert_eq!(disassembled("9223372036854775807 + 1"),
+               "0000    1 CONSTANT            0 '9223372036854775807'\n\
+                0002    | CONSTANT            1 '1'\n\
+                0004    | ADD\n\
+                0005    | RETURN\n");
+    assert_eq!(disassembled("1 % 0"),
+               "0000    1 CONSTANT            0 '1'\n\
+                0002    | CONSTANT            1 '0'\n\
+                0004    | MODULO\n\
+                0005    | RETURN\n");
+}
+
+#[test]
+fn opt_level_none_keeps_every_instruction() {
+    let chunk = Compiler::with_opt_level(String::from("(-1 + 2) * 3 - -4"), OptLevel::None).compile().unwrap();
+
+    assert_eq!(chunk.code.len(), 14);
+    assert_eq!(chunk.constants.len(), 4);
+}
+
+#[test]
+fn drops_empty_interpolation_parts() {
+    assert_eq!(disassembled("\"${-\"a\"}\"\n+ \"!\""),
+               "0000    1 CONSTANT            0 'a'\n\
+                0002    | NEGATE\n\
+                0003    | BUILD_STRING        1\n\
+                0005    2 CONSTANT            1 '!'\n\
+                0007    | ADD\n\
+                0008    | RETURN\n");
+
+    let chunk = Compiler::with_opt_level(String::from("\"${-\"a\"}\""), OptLevel::None).compile().unwrap();
+    assert_eq!(chunk.constants.len(), 3);
+}
+
+#[test]
+fn compiles_calls() {
+    assert_eq!(disassembled("f(1 + 2, g())"),
+               "0000    1 GET_GLOBAL          0 'f'\n\
+                0002    | CONSTANT            1 '3'\n\
+                0004    | GET_GLOBAL          2 'g'\n\
+                0006    | CALL                0\n\
+                0008    | CALL                2\n\
+                0010    | RETURN\n");
+}
+
+#[test]
+fn compiles_method_calls() {
+    assert_eq!(disassembled("file.read(1 + 2)"),
+               "0000    1 GET_GLOBAL          0 'file'\n\
+                0002    | CONSTANT            2 '3'\n\
+                0004    | INVOKE           (1 args)    1 'read'\n\
+                0007    | RETURN\n");
+}
@@ -0,0 +1,229 @@
+use crate::scanner::*;
+use crate::syntax::SyntaxTree;
+
+const INDENT: &str = "    ";
+
+/// Formats Lox source: one statement per line, four space indentation per
+/// brace, braces on the line of the construct they belong to, single
+/// spaces around binary operators and at most one blank line in a row.
+/// Comments are kept where they were written. Source that does not scan
+/// cleanly is left alone and its first error returned instead.
+pub fn format(source: &str) -> Result<String, String> {
+    let tree = SyntaxTree::parse(String::from(source));
+    let tokens = tree.tokens();
+
+    if let Some(error) = tokens.iter().find(|token| token.token_type == TokenType::Error) {
+        return Err(format!("[line {}] Error: {}", error.line, error.lexeme));
+    }
+
+    let mut formatter = Formatter::new(&tree);
+    for token in tokens {
+        formatter.token(token);
+    }
+    Ok(formatter.finish())
+}
+
+struct Formatter<'a> {
+    tree: &'a SyntaxTree,
+    output: String,
+    previous: Option<&'a Token>,
+    // Whether the previous token was a prefix `-`.
+    unary: bool,
+    indent: usize,
+    parens: usize,
+    at_line_start: bool,
+    // A line break is due after `;`, `{` or `}`.
+    break_pending: bool,
+    // A line break is due after a line comment, whatever comes next.
+    comment_break: bool,
+    // An inline block comment was just written.
+    after_comment: bool
+}
+
+impl<'a> Formatter<'a> {
+    fn new(tree: &'a SyntaxTree) -> Self {
+        Self {
+            tree,
+            output: String::new(),
+            previous: None,
+            unary: false,
+            indent: 0,
+            parens: 0,
+            at_line_start: true,
+            break_pending: false,
+            comment_break: false,
+            after_comment: false
+        }
+    }
+
+    fn token(&mut self, token: &'a Token) {
+        let newlines = self.leading_trivia(token);
+
+        if token.token_type == TokenType::EOF {
+            return;
+        }
+
+        if token.token_type == TokenType::RightBrace {
+            self.indent = self.indent.saturating_sub(1);
+        }
+
+        let empty_block = token.token_type == TokenType::RightBrace &&
+                          self.previous_is(TokenType::LeftBrace) &&
+                          !self.comment_break;
+
+        if self.comment_break || (self.break_pending && !self.stays_on_line(token) && !empty_block) {
+            self.line_break(newlines > 1 && token.token_type != TokenType::RightBrace);
+        } else if self.after_comment || self.previous.is_some_and(|previous| self.spaced(previous, token)) {
+            self.write(" ");
+        }
+
+        self.write(self.tree.text(token));
+        self.unary = token.token_type == TokenType::Minus && self.prefix_position();
+        self.previous = Some(token);
+        self.break_pending = false;
+        self.after_comment = false;
+
+        match token.token_type {
+            TokenType::LeftParen => self.parens += 1,
+            TokenType::RightParen => self.parens = self.parens.saturating_sub(1),
+            TokenType::LeftBrace => {
+                self.indent += 1;
+                self.break_pending = true;
+            }
+            TokenType::RightBrace => self.break_pending = true,
+            TokenType::Semicolon => self.break_pending = self.parens == 0,
+            _ => {}
+        }
+
+        self.trailing_trivia(token);
+    }
+
+    // Writes the comments in front of a token, each on its own line, and
+    // returns the number of line breaks seen since the last of them.
+    fn leading_trivia(&mut self, token: &Token) -> usize {
+        let mut newlines = 0;
+
+        for trivia in &token.leading_trivia {
+            match trivia.kind {
+                TriviaKind::Newline => newlines += 1,
+                TriviaKind::Whitespace => {}
+                TriviaKind::LineComment | TriviaKind::BlockComment => {
+                    if !self.output.is_empty() {
+                        self.line_break(newlines > 1);
+                    }
+                    self.write(trivia.text.trim_end());
+                    self.comment_break = trivia.kind == TriviaKind::LineComment;
+                    self.after_comment = !self.comment_break;
+                    newlines = 0;
+                }
+            }
+        }
+
+        // A block comment alone on its line keeps its line.
+        if self.after_comment && newlines > 0 {
+            self.comment_break = true;
+            self.after_comment = false;
+        }
+        newlines
+    }
+
+    fn trailing_trivia(&mut self, token: &Token) {
+        for trivia in &token.trailing_trivia {
+            match trivia.kind {
+                TriviaKind::LineComment => {
+                    self.write(" ");
+                    self.write(trivia.text.trim_end());
+                    self.comment_break = true;
+                }
+                TriviaKind::BlockComment => {
+                    self.write(" ");
+                    self.write(&trivia.text);
+                    self.after_comment = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let length = self.output.trim_end().len();
+        self.output.truncate(length);
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.at_line_start {
+            for _ in 0..self.indent {
+                self.output.push_str(INDENT);
+            }
+            self.at_line_start = false;
+        }
+        self.output.push_str(text);
+    }
+
+    // Ends the current line, followed by a blank one if the source had one
+    // here and it is not at the start of a block.
+    fn line_break(&mut self, blank: bool) {
+        let length = self.output.trim_end_matches(' ').len();
+        self.output.truncate(length);
+
+        self.output.push('\n');
+        if blank && !self.previous_is(TokenType::LeftBrace) {
+            self.output.push('\n');
+        }
+        self.at_line_start = true;
+        self.comment_break = false;
+    }
+
+    fn previous_is(&self, token_type: TokenType) -> bool {
+        self.previous.is_some_and(|previous| previous.token_type == token_type)
+    }
+
+    // Tokens that follow a closing brace on the same line.
+    fn stays_on_line(&self, token: &Token) -> bool {
+        self.previous_is(TokenType::RightBrace) &&
+        matches!(token.token_type, TokenType::Else | TokenType::Semicolon |
+                                   TokenType::Comma | TokenType::RightParen)
+    }
+
+    // Whether an operator in the current position would be a prefix one.
+    fn prefix_position(&self) -> bool {
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => return true
+        };
+
+        !matches!(previous.token_type,
+                  TokenType::Number | TokenType::String | TokenType::Identifier |
+                  TokenType::RightParen | TokenType::True | TokenType::False |
+                  TokenType::Nil | TokenType::This | TokenType::Super)
+    }
+
+    fn spaced(&self, previous: &Token, token: &Token) -> bool {
+        // Parts of an interpolated string hug the expression inside.
+        if matches!(token.token_type, TokenType::String | TokenType::Interpolation) &&
+           token.lexeme.starts_with('}') {
+            return false;
+        }
+
+        match (previous.token_type, token.token_type) {
+            (_, TokenType::Semicolon) | (_, TokenType::Comma) |
+            (_, TokenType::RightParen) | (_, TokenType::Dot) => false,
+            (TokenType::LeftParen, _) | (TokenType::Dot, _) |
+            (TokenType::Interpolation, _) | (TokenType::Bang, _) |
+            (TokenType::Tilde, _) => false,
+            (TokenType::LeftBrace, TokenType::RightBrace) => false,
+            (TokenType::Minus, _) => !self.unary,
+            (TokenType::Identifier, TokenType::LeftParen) |
+            (TokenType::RightParen, TokenType::LeftParen) |
+            (TokenType::This, TokenType::LeftParen) => false,
+            _ => true
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,41 @@
+use super::format;
+
+fn assert_formats(source: &str, expected: &str) {
+    let formatted = format(source).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(format(&formatted).unwrap(), expected, "formatting is not idempotent");
+}
+
+#[test]
+fn operators() {
+    assert_formats("(-1+2)*3- -4", "(-1 + 2) * 3 - -4\n");
+    assert_formats("  a  =  !b  ==  ( c-d )  ;", "a = !b == (c - d);\n");
+    assert_formats("print\"${ 1+2 }\";", "print \"${1 + 2}\";\n");
+    assert_formats("~ a&0xFF<<2**-b", "~a & 0xFF << 2 ** -b\n");
+}
+
+#[test]
+fn blocks() {
+    assert_formats("fun add(a,b){return a+b;}\nclass A < B\n{\n  init ( ) { this.x=1 ; }\n}",
+                   "fun add(a, b) {\n    return a + b;\n}\nclass A < B {\n    init() {\n        this.x = 1;\n    }\n}\n");
+    assert_formats("if (a) { b(); }\nelse {}", "if (a) {\n    b();\n} else {}\n");
+    assert_formats("for (var i = 0;i < 3;i = i + 1) print i;", "for (var i = 0; i < 3; i = i + 1) print i;\n");
+}
+
+#[test]
+fn blank_lines() {
+    assert_formats("\n\na;\n\n\n\nb;\n{\n\n  c;\n\n}\n\n\n", "a;\n\nb;\n{\n    c;\n}\n");
+}
+
+#[test]
+fn comments() {
+    assert_formats("// header\n\nvar a = 1;   // one  \n{\n// inside\n  b; /* note */ c;\n}\n/* tail */\n",
+                   "// header\n\nvar a = 1; // one\n{\n    // inside\n    b; /* note */\n    c;\n}\n/* tail */\n");
+    assert_formats("a = /* inline */ 1 + // why\n2;", "a = /* inline */ 1 + // why\n2;\n");
+}
+
+#[test]
+fn scan_errors() {
+    assert!(format("\"unterminated").is_err());
+    assert!(format("var a = 1;\n1.").is_err());
+}
@@ -3,3 +3,7 @@ pub mod chunk;
 pub mod vm;
 pub mod value;
 pub mod compiler;
+pub mod syntax;
+pub mod formatter;
+pub mod lint;
+pub mod ast;
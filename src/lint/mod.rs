@@ -0,0 +1,498 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::compiler::diagnostic::*;
+use crate::scanner::*;
+use crate::syntax::*;
+
+#[derive(Debug)]
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Copy, Clone)]
+pub enum Lint {
+    UnusedVariable,
+    UnreachableCode,
+    ShadowedVariable,
+    AssignmentInCondition,
+    SelfComparison,
+    WrongArgumentCount
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::UnusedVariable,
+        Lint::UnreachableCode,
+        Lint::ShadowedVariable,
+        Lint::AssignmentInCondition,
+        Lint::SelfComparison,
+        Lint::WrongArgumentCount
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused_variable",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::ShadowedVariable => "shadowed_variable",
+            Lint::AssignmentInCondition => "assignment_in_condition",
+            Lint::SelfComparison => "self_comparison",
+            Lint::WrongArgumentCount => "wrong_argument_count"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+/// Which lints are reported. Every lint is enabled by default.
+#[derive(Debug)]
+#[derive(Default, Clone)]
+pub struct LintConfig {
+    disabled: HashSet<Lint>
+}
+
+impl LintConfig {
+    /// Reads a config with one `lint_name = on|off` setting per line.
+    /// Everything after a `#` is a comment.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config: LintConfig = Default::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = || format!("[line {}] Error: Expect 'lint_name = on|off'.", number + 1);
+            let (name, setting) = line.split_once('=').ok_or_else(error)?;
+            let lint = Lint::from_name(name.trim())
+                .ok_or_else(|| format!("[line {}] Error: Unknown lint '{}'.", number + 1, name.trim()))?;
+
+            match setting.trim() {
+                "on" => config.enable(lint),
+                "off" => config.disable(lint),
+                _ => return Err(error())
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn enable(&mut self, lint: Lint) {
+        self.disabled.remove(&lint);
+    }
+
+    pub fn disable(&mut self, lint: Lint) {
+        self.disabled.insert(lint);
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        !self.disabled.contains(&lint)
+    }
+}
+
+/// Checks source for likely mistakes and returns its scan errors and lint
+/// warnings in source order. A comment containing `lint: allow(name, ...)`
+/// silences the named lints on its own line and the one after it, and
+/// naming a lint that does not exist is an error, as in a config file.
+///
+/// The checks are syntactic: they look at declarations, blocks and calls
+/// in the lossless syntax tree rather than at compiled code.
+pub fn lint(source: &str, config: &LintConfig) -> Vec<Diagnostic> {
+    let tree = SyntaxTree::parse(String::from(source));
+    let mut linter = Linter::new(&tree, config);
+
+    linter.collect_arities(tree.nodes());
+    linter.walk(tree.nodes(), false);
+
+    let mut diagnostics: Vec<Diagnostic> = tree.tokens().into_iter()
+        .filter(|token| token.token_type == TokenType::Error)
+        .map(|token| Diagnostic::at(Severity::Error, token, token.lexeme.clone()))
+        .collect();
+    diagnostics.append(&mut linter.warnings);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    diagnostics
+}
+
+struct Variable<'a> {
+    token: &'a Token,
+    used: bool
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    // The innermost scope is last, the first one holds globals.
+    scopes: Vec<Vec<Variable<'a>>>,
+    // Arity of each function declared with `fun`, or None if the name is
+    // declared more than once with different arities.
+    arities: HashMap<&'a str, Option<usize>>,
+    allowed: HashSet<(usize, Lint)>,
+    warnings: Vec<Diagnostic>
+}
+
+impl<'a> Linter<'a> {
+    fn new(tree: &'a SyntaxTree, config: &'a LintConfig) -> Self {
+        let mut warnings = Vec::new();
+        let allowed = allowed_lints(tree, &mut warnings);
+
+        Self {
+            config,
+            scopes: vec![Vec::new()],
+            arities: HashMap::new(),
+            allowed,
+            warnings
+        }
+    }
+
+    fn warn(&mut self, lint: Lint, token: &Token, message: String) {
+        if !self.config.is_enabled(lint) || self.allowed.contains(&(token.line, lint)) {
+            return;
+        }
+        let message = format!("{} [{}]", message, lint.name());
+        self.warnings.push(Diagnostic::at(Severity::Warning, token, message));
+    }
+
+    fn collect_arities(&mut self, nodes: &'a [Node]) {
+        for (i, node) in nodes.iter().enumerate() {
+            if let Some((name, params, _)) = function_at(nodes, i) {
+                let arity = argument_count(params);
+                let entry = self.arities.entry(&name.lexeme).or_insert(Some(arity));
+                if *entry != Some(arity) {
+                    *entry = None;
+                }
+            }
+            if let Node::Group { children, .. } = node {
+                self.collect_arities(children);
+            }
+        }
+    }
+
+    fn walk(&mut self, nodes: &'a [Node], class_body: bool) {
+        // Set once a `return` at this level reaches its `;`.
+        let mut returned = false;
+        let mut in_return = false;
+        let mut class_next = false;
+        let mut i = 0;
+
+        while i < nodes.len() {
+            let node = &nodes[i];
+
+            if returned {
+                let token = first_token(node);
+                if token.token_type != TokenType::EOF {
+                    self.warn(Lint::UnreachableCode, token, String::from("Unreachable code."));
+                }
+                returned = false;
+            }
+
+            if let Some((name, params, body)) = function_at(nodes, i) {
+                self.declare(name, true);
+                self.function(params, body);
+                i += 4;
+                continue;
+            }
+            if class_body {
+                if let Some((params, body)) = method_at(nodes, i) {
+                    self.function(params, body);
+                    i += 3;
+                    continue;
+                }
+            }
+
+            match node {
+                Node::Token(token) => match token.token_type {
+                    TokenType::Var => {
+                        if let Some(name) = token_at(nodes, i + 1, TokenType::Identifier) {
+                            self.declare(name, false);
+                            i += 1;
+                        }
+                    }
+                    TokenType::Class => {
+                        if let Some(name) = token_at(nodes, i + 1, TokenType::Identifier) {
+                            self.declare(name, true);
+                            i += 1;
+                        }
+                        class_next = true;
+                    }
+                    TokenType::Identifier => self.identifier(nodes, i),
+                    TokenType::Return => in_return = !conditional_body(nodes, i),
+                    TokenType::Semicolon => {
+                        returned = in_return;
+                        in_return = false;
+                    }
+                    TokenType::If | TokenType::While => {
+                        if let Some(condition) = paren_group_at(nodes, i + 1) {
+                            self.condition(condition);
+                        }
+                    }
+                    TokenType::For => {
+                        if let Some(clauses) = paren_group_at(nodes, i + 1) {
+                            if let Some(condition) = clauses.split(is_semicolon).nth(1) {
+                                self.condition(condition);
+                            }
+                        }
+                    }
+                    TokenType::EqualEqual | TokenType::BangEqual |
+                    TokenType::Less | TokenType::LessEqual |
+                    TokenType::Greater | TokenType::GreaterEqual => self.self_comparison(nodes, i),
+                    _ => {}
+                },
+                Node::Group { open, children, .. } => {
+                    if open.token_type == TokenType::LeftBrace {
+                        self.scopes.push(Vec::new());
+                        self.walk(children, class_next);
+                        self.end_scope();
+                    } else {
+                        self.walk(children, false);
+                    }
+                    class_next = false;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    fn function(&mut self, params: &'a [Node], body: &'a [Node]) {
+        let mut scope = Vec::new();
+        for param in params {
+            if let Node::Token(token) = param {
+                if token.token_type == TokenType::Identifier {
+                    scope.push(Variable { token, used: true });
+                }
+            }
+        }
+
+        self.scopes.push(scope);
+        self.walk(body, false);
+        self.end_scope();
+    }
+
+    fn declare(&mut self, name: &'a Token, used: bool) {
+        let enclosing = &self.scopes[..self.scopes.len() - 1];
+
+        let shadows = !enclosing.is_empty() &&
+                      enclosing.iter().flatten().any(|variable| variable.token.lexeme == name.lexeme);
+        if shadows {
+            self.warn(Lint::ShadowedVariable, name,
+                      format!("Variable '{}' shadows a variable in an enclosing scope.", name.lexeme));
+        }
+
+        self.scopes.last_mut().unwrap().push(Variable { token: name, used });
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap_or_default();
+
+        for variable in scope.iter().filter(|variable| !variable.used) {
+            self.warn(Lint::UnusedVariable, variable.token,
+                      format!("Local variable '{}' is never used.", variable.token.lexeme));
+        }
+    }
+
+    fn identifier(&mut self, nodes: &'a [Node], i: usize) {
+        let token = match &nodes[i] {
+            Node::Token(token) => token,
+            _ => return
+        };
+
+        // Properties and methods are not variables.
+        if i > 0 && token_at(nodes, i - 1, TokenType::Dot).is_some() {
+            return;
+        }
+
+        if let Some(arguments) = paren_group_at(nodes, i + 1) {
+            self.call(token, arguments);
+        }
+
+        // Assigning to a variable does not count as using it.
+        if token_at(nodes, i + 1, TokenType::Equal).is_some() {
+            return;
+        }
+
+        let variable = self.scopes.iter_mut().rev().flatten()
+            .find(|variable| variable.token.lexeme == token.lexeme);
+        if let Some(variable) = variable {
+            variable.used = true;
+        }
+    }
+
+    fn call(&mut self, callee: &Token, arguments: &[Node]) {
+        let shadowed = self.scopes.iter().skip(1).flatten()
+            .any(|variable| variable.token.lexeme == callee.lexeme);
+        let arity = match self.arities.get(callee.lexeme.as_str()) {
+            Some(Some(arity)) if !shadowed => *arity,
+            _ => return
+        };
+
+        let count = argument_count(arguments);
+        if count != arity {
+            self.warn(Lint::WrongArgumentCount, callee,
+                      format!("Expected {} arguments but got {}.", arity, count));
+        }
+    }
+
+    fn condition(&mut self, condition: &[Node]) {
+        for node in condition {
+            if let Node::Token(token) = node {
+                if token.token_type == TokenType::Equal {
+                    self.warn(Lint::AssignmentInCondition, token,
+                              String::from("Assignment in condition, did you mean '=='?"));
+                }
+            }
+        }
+    }
+
+    fn self_comparison(&mut self, nodes: &[Node], i: usize) {
+        if i == 0 {
+            return;
+        }
+
+        let left = token_at(nodes, i - 1, TokenType::Identifier);
+        let right = token_at(nodes, i + 1, TokenType::Identifier);
+        // `a.x == x` and `x == x()` compare different things.
+        let qualified = (i > 1 && token_at(nodes, i - 2, TokenType::Dot).is_some()) ||
+                        token_at(nodes, i + 2, TokenType::Dot).is_some() ||
+                        paren_group_at(nodes, i + 2).is_some();
+
+        if let (Some(left), Some(right), false) = (left, right, qualified) {
+            if left.lexeme == right.lexeme {
+                self.warn(Lint::SelfComparison, left,
+                          format!("Comparison of '{}' with itself.", left.lexeme));
+            }
+        }
+    }
+}
+
+fn token_at(nodes: &[Node], i: usize, token_type: TokenType) -> Option<&Token> {
+    match nodes.get(i) {
+        Some(Node::Token(token)) if token.token_type == token_type => Some(token),
+        _ => None
+    }
+}
+
+fn group_at(nodes: &[Node], i: usize, open_type: TokenType) -> Option<&[Node]> {
+    match nodes.get(i) {
+        Some(Node::Group { open, children, .. }) if open.token_type == open_type => Some(children),
+        _ => None
+    }
+}
+
+fn paren_group_at(nodes: &[Node], i: usize) -> Option<&[Node]> {
+    group_at(nodes, i, TokenType::LeftParen)
+}
+
+// Matches `fun name(params) { body }` starting at `i`.
+fn function_at(nodes: &[Node], i: usize) -> Option<(&Token, &[Node], &[Node])> {
+    token_at(nodes, i, TokenType::Fun)?;
+    let name = token_at(nodes, i + 1, TokenType::Identifier)?;
+    let params = paren_group_at(nodes, i + 2)?;
+    let body = group_at(nodes, i + 3, TokenType::LeftBrace)?;
+    Some((name, params, body))
+}
+
+// Matches a method, `name(params) { body }`, in a class body.
+fn method_at(nodes: &[Node], i: usize) -> Option<(&[Node], &[Node])> {
+    token_at(nodes, i, TokenType::Identifier)?;
+    let params = paren_group_at(nodes, i + 1)?;
+    let body = group_at(nodes, i + 2, TokenType::LeftBrace)?;
+    Some((params, body))
+}
+
+// Whether the statement starting at `i` is the body of an `if`, `else`,
+// `while` or `for` rather than a statement of the block itself.
+fn conditional_body(nodes: &[Node], i: usize) -> bool {
+    if i == 0 {
+        return false;
+    }
+    if token_at(nodes, i - 1, TokenType::Else).is_some() {
+        return true;
+    }
+
+    i > 1 && paren_group_at(nodes, i - 1).is_some() &&
+    matches!(&nodes[i - 2], Node::Token(token)
+             if matches!(token.token_type, TokenType::If | TokenType::While | TokenType::For))
+}
+
+fn argument_count(arguments: &[Node]) -> usize {
+    let arguments: Vec<&Node> = arguments.iter()
+        .filter(|node| !matches!(node, Node::Token(token) if token.token_type == TokenType::EOF))
+        .collect();
+
+    if arguments.is_empty() {
+        return 0;
+    }
+    arguments.iter()
+        .filter(|node| matches!(node, Node::Token(token) if token.token_type == TokenType::Comma))
+        .count() + 1
+}
+
+fn is_semicolon(node: &Node) -> bool {
+    matches!(node, Node::Token(token) if token.token_type == TokenType::Semicolon)
+}
+
+fn first_token(node: &Node) -> &Token {
+    match node {
+        Node::Token(token) => token,
+        Node::Group { open, .. } => open
+    }
+}
+
+// Lines on which a `lint: allow(...)` comment silences lints. Unknown lint
+// names are reported to `errors`.
+fn allowed_lints(tree: &SyntaxTree, errors: &mut Vec<Diagnostic>) -> HashSet<(usize, Lint)> {
+    let source = tree.source();
+    // Trivia comes in source order, so the line count only moves forward.
+    let mut line = 1;
+    let mut scanned = 0;
+    let mut line_at = |offset: usize| {
+        line += source[scanned..offset].matches('\n').count();
+        scanned = offset;
+        line
+    };
+    let mut allowed = HashSet::new();
+
+    for token in tree.tokens() {
+        let leading: usize = token.leading_trivia.iter().map(|trivia| trivia.text.len()).sum();
+        let mut offset = token.span.start - leading;
+
+        for trivia in &token.leading_trivia {
+            allow_in_comment(&trivia.text, line_at(offset), &mut allowed, errors);
+            offset += trivia.text.len();
+        }
+
+        let mut offset = token.span.end;
+        for trivia in &token.trailing_trivia {
+            allow_in_comment(&trivia.text, line_at(offset), &mut allowed, errors);
+            offset += trivia.text.len();
+        }
+    }
+    allowed
+}
+
+fn allow_in_comment(comment: &str, line: usize, allowed: &mut HashSet<(usize, Lint)>,
+                    errors: &mut Vec<Diagnostic>) {
+    let names = comment.split("lint: allow(").nth(1)
+                       .and_then(|rest| rest.split(')').next());
+
+    let names = match names {
+        Some(names) => names,
+        None => return
+    };
+
+    for name in names.split(',').map(str::trim) {
+        let lint = match Lint::from_name(name) {
+            Some(lint) => lint,
+            None => {
+                errors.push(Diagnostic {
+                    severity: Severity::Error,
+                    line,
+                    location: None,
+                    message: format!("Unknown lint '{}'.", name)
+                });
+                continue;
+            }
+        };
+        allowed.insert((line, lint));
+        allowed.insert((line + 1, lint));
+    }
+}
+
+#[cfg(test)]
+mod test;
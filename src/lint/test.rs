@@ -0,0 +1,91 @@
+use super::{lint, Lint, LintConfig};
+use crate::compiler::diagnostic::Severity;
+
+fn warnings(source: &str) -> Vec<String> {
+    lint(source, &Default::default()).iter().map(|diagnostic| diagnostic.to_string()).collect()
+}
+
+#[test]
+fn unused_variable() {
+    assert_eq!(warnings("var global = 1;\nfun f(a) {\n  var used = a;\n  var unused = 2;\n  used = 3;\n  return used;\n}"),
+               ["[line 4] Warning at unused: Local variable 'unused' is never used. [unused_variable]"]);
+    assert!(warnings("{ var a = 1; { print \"${a}\"; } }").is_empty());
+}
+
+#[test]
+fn unreachable_code() {
+    assert_eq!(warnings("fun f() {\n  return 1;\n  print 2;\n  print 3;\n}"),
+               ["[line 3] Warning at print: Unreachable code. [unreachable_code]"]);
+    assert!(warnings("fun f(a) {\n  if (a) return 1;\n  while (a) return 2;\n  if (a) {} else return 3;\n  return 4;\n}").is_empty());
+}
+
+#[test]
+fn shadowed_variable() {
+    assert_eq!(warnings("var a = 1;\nfun f(b) {\n  { var b = a; print b; }\n}"),
+               ["[line 3] Warning at b: Variable 'b' shadows a variable in an enclosing scope. [shadowed_variable]"]);
+}
+
+#[test]
+fn assignment_in_condition() {
+    assert_eq!(warnings("if (a = b) print a;\nwhile (a == b) {}\nfor (;a = b;) {}"),
+               ["[line 1] Warning at =: Assignment in condition, did you mean '=='? [assignment_in_condition]",
+                "[line 3] Warning at =: Assignment in condition, did you mean '=='? [assignment_in_condition]"]);
+}
+
+#[test]
+fn self_comparison() {
+    assert_eq!(warnings("print a == a;\nprint a.x == x;\nprint x < x();\nprint b >= b;"),
+               ["[line 1] Warning at a: Comparison of 'a' with itself. [self_comparison]",
+                "[line 4] Warning at b: Comparison of 'b' with itself. [self_comparison]"]);
+}
+
+#[test]
+fn wrong_argument_count() {
+    assert_eq!(warnings("fun add(a, b) { return a + b; }\nadd(1);\nadd(1, 2);\nadd();\nobj.add(1);"),
+               ["[line 2] Warning at add: Expected 2 arguments but got 1. [wrong_argument_count]",
+                "[line 4] Warning at add: Expected 2 arguments but got 0. [wrong_argument_count]"]);
+}
+
+#[test]
+fn class_methods() {
+    assert!(warnings("class A < B {\n  init(x) { this.x = x; }\n  get() { return this.x; }\n}").is_empty());
+    assert_eq!(warnings("class A {\n  m() {\n    var unused;\n  }\n}").len(), 1);
+}
+
+#[test]
+fn scan_errors() {
+    let diagnostics = lint("print 1.;", &Default::default());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+}
+
+#[test]
+fn config() {
+    let mut config = LintConfig::parse("# comment\nself_comparison = off\nunused_variable = off\nunused_variable = on\n").unwrap();
+    assert!(!config.is_enabled(Lint::SelfComparison));
+    assert!(config.is_enabled(Lint::UnusedVariable));
+    assert!(lint("print a == a;", &config).is_empty());
+
+    config.enable(Lint::SelfComparison);
+    assert_eq!(lint("print a == a;", &config).len(), 1);
+
+    assert!(LintConfig::parse("no_such_lint = off").is_err());
+    assert!(LintConfig::parse("self_comparison = maybe").is_err());
+}
+
+#[test]
+fn inline_allow() {
+    assert!(warnings("print a == a; // lint: allow(self_comparison)").is_empty());
+    assert!(warnings("/* lint: allow(unused_variable, self_comparison) */\n{ var x = x == x; }").is_empty());
+    assert_eq!(warnings("// lint: allow(unused_variable)\nprint a == a;").len(), 1);
+    assert_eq!(warnings("print 1;\n// lint: allow(unused_varible, self_comparison)\nprint a == a;"),
+               ["[line 2] Error: Unknown lint 'unused_varible'."]);
+
+    let source = "print 1;\n".repeat(50) +
+                 "print a == a; // lint: allow(self_comparison)\n\n" +
+                 "/* lint: allow(self_comparison)\n */\nprint b == b;\n" +
+                 "// lint: allow(self_comparison)\n\nprint c == c;";
+    assert_eq!(warnings(&source),
+               ["[line 55] Warning at b: Comparison of 'b' with itself. [self_comparison]",
+                "[line 58] Warning at c: Comparison of 'c' with itself. [self_comparison]"]);
+}
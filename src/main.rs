@@ -1,13 +1,28 @@
 use std::io::{self, Write};
 use std::env;
 use std::fs;
+use std::process;
 
+use rlox::chunk::serialize;
+use rlox::compiler::Compiler;
+use rlox::formatter;
+use rlox::lint::{self, LintConfig};
 use rlox::vm::VM;
 
 fn main() {
     let args: Vec<String>  = env::args().collect();    
     let mut vm: VM = Default::default();
 
+    if args.len() > 1 && args[1] == "fmt" {
+        process::exit(fmt(&args[2..]));
+    }
+    if args.len() > 1 && args[1] == "lint" {
+        process::exit(lint(&args[2..]));
+    }
+    if args.len() > 1 && args[1] == "compile" {
+        process::exit(compile(&args[2..]));
+    }
+
     if args.len() == 1 {
         loop {
             print!("> ");
@@ -21,6 +36,18 @@ fn main() {
                 Err(error) => println!("error: {}", error),
             }
         }
+    } else if args[1].ends_with(".loxc") {
+        let bytes = fs::read(&args[1]).expect("Cannot read file.");
+
+        match serialize::deserialize(&bytes) {
+            Ok(chunk) => {
+                vm.interpret_chunk(chunk, &mut io::stdout());
+            }
+            Err(error) => {
+                eprintln!("{}: {}", args[1], error);
+                process::exit(65);
+            }
+        }
     } else {
         let filename = &args[1];
         let contents = fs::read_to_string(filename).expect("Cannot read file.");
@@ -28,3 +55,133 @@ fn main() {
         vm.interpret(contents, &mut io::stdout());
     }
 }
+
+// `rlox compile <file> -o <output>` writes the file's bytecode to the
+// output, which `rlox <output>` then runs without recompiling.
+fn compile(args: &[String]) -> i32 {
+    let (filename, output) = match args {
+        [filename, flag, output] if flag == "-o" => (filename, output),
+        _ => {
+            eprintln!("Usage: rlox compile <file> -o <output>");
+            return 64;
+        }
+    };
+
+    let contents = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            return 1;
+        }
+    };
+
+    let chunk = match Compiler::new(contents).compile() {
+        Ok(chunk) => chunk,
+        Err(_) => return 65
+    };
+
+    let bytes = match serialize::serialize(&chunk) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!("{}: {}", filename, error);
+            return 65;
+        }
+    };
+
+    if let Err(error) = fs::write(output, bytes) {
+        eprintln!("{}: {}", output, error);
+        return 1;
+    }
+    0
+}
+
+// `rlox fmt [--check] <files>` rewrites the files in place, or with
+// `--check` lists the ones that would change. Exits non-zero if any file
+// would change or could not be formatted.
+fn fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+
+    if files.is_empty() {
+        eprintln!("Usage: rlox fmt [--check] <files>");
+        return 64;
+    }
+
+    let mut status = 0;
+    for filename in files {
+        let result = fs::read_to_string(filename)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| {
+                let formatted = formatter::format(&contents)?;
+                Ok((formatted != contents, formatted))
+            });
+
+        match result {
+            Ok((false, _)) => {}
+            Ok((true, _)) if check => {
+                println!("{}", filename);
+                status = 1;
+            }
+            Ok((true, formatted)) => {
+                if let Err(error) = fs::write(filename, formatted) {
+                    eprintln!("{}: {}", filename, error);
+                    status = 1;
+                }
+            }
+            Err(error) => {
+                eprintln!("{}: {}", filename, error);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+// `rlox lint [--config <file>] <files>` prints scan errors and warnings for
+// each file and exits non-zero if there were any.
+fn lint(args: &[String]) -> i32 {
+    let mut config: LintConfig = Default::default();
+    let mut files = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg != "--config" {
+            files.push(arg);
+            continue;
+        }
+
+        let parsed = args.next()
+            .ok_or_else(|| String::from("Expect a file after --config."))
+            .and_then(|path| fs::read_to_string(path).map_err(|error| error.to_string()))
+            .and_then(|text| LintConfig::parse(&text));
+        match parsed {
+            Ok(parsed) => config = parsed,
+            Err(error) => {
+                eprintln!("{}", error);
+                return 64;
+            }
+        }
+    }
+
+    if files.is_empty() {
+        eprintln!("Usage: rlox lint [--config <file>] <files>");
+        return 64;
+    }
+
+    let mut status = 0;
+    for filename in files {
+        match fs::read_to_string(filename) {
+            Ok(contents) => {
+                for diagnostic in lint::lint(&contents, &config) {
+                    eprintln!("{}: {}", filename, diagnostic);
+                    status = 1;
+                }
+            }
+            Err(error) => {
+                eprintln!("{}: {}", filename, error);
+                status = 1;
+            }
+        }
+    }
+    status
+}
@@ -9,16 +9,25 @@ pub enum TokenType {
   LeftParen, RightParen,
   LeftBrace, RightBrace,
   Comma, Dot, Minus, Plus,
-  Semicolon, Slash, Star,
+  Semicolon, Slash, Star, Percent,
+  Ampersand, Pipe, Caret, Tilde,
 
   // One or two character tokens.
   Bang, BangEqual,
   Equal, EqualEqual,
   Greater, GreaterEqual,
   Less, LessEqual,
+  // Integer division, `~/`.
+  TildeSlash,
+  // Exponentiation, `**`.
+  StarStar,
+  // Shifts, `<<` and `>>`.
+  LessLess, GreaterGreater,
 
   // Literals.
   Identifier, String, Number,
+  // A string part that ends in `${`, before an interpolated expression.
+  Interpolation,
 
   // Keywords.
   And, Class, Else, False,
@@ -34,16 +43,52 @@ impl Default for Token {
         Token {
             token_type: TokenType::Default,
             lexeme: String::from("Default"),
-            line: 0
+            line: 0,
+            span: Default::default(),
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new()
         }
     }
 }
 
+/// Byte range of a token in the scanned source.
 #[derive(Debug)]
+#[derive(Default, PartialEq)]
+#[derive(Copy, Clone)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Copy, Clone)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment
+}
+
+/// Source text between tokens, kept only when scanning with trivia.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: usize,
+    pub span: Span,
+    // Trivia before the token, from the start of its line onwards, and
+    // after it, up to the end of its line.
+    pub leading_trivia: Vec<Trivia>,
+    pub trailing_trivia: Vec<Trivia>,
 }
 
 #[derive(Debug)]
@@ -53,16 +98,23 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: usize,
+    // Unclosed brace depth of each `${` we are currently inside of.
+    interpolation: Vec<usize>,
+    trivia: bool,
+    eof_emitted: bool,
 }
 
 
 impl Token {
     fn new(token_type: TokenType, lexeme: String,
-           line: usize) -> Self {
+           line: usize, span: Span) -> Self {
         Self {
             token_type,
             lexeme,
-            line
+            line,
+            span,
+            leading_trivia: Vec::new(),
+            trailing_trivia: Vec::new()
         }
     }
 }
@@ -73,10 +125,30 @@ impl Scanner {
             contents,
             start: 0,
             current: 0,
-            line: 1
+            line: 1,
+            interpolation: Vec::new(),
+            trivia: false,
+            eof_emitted: false
         }
     }
 
+    /// Creates a scanner that attaches whitespace and comments to the
+    /// tokens around them and ends with an EOF token holding whatever trivia
+    /// follows the last token, so that no source text is lost.
+    pub fn with_trivia(contents: String) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(contents)
+        }
+    }
+
+    /// A token for the end of the source, which the iterator signals with
+    /// `None` unless scanning with trivia.
+    pub fn eof(&self) -> Token {
+        Token::new(TokenType::EOF, String::new(), self.line,
+                   Span { start: self.contents.len(), end: self.contents.len() })
+    }
+
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
         for tokens in self {
             println!("{:?}", tokens);
@@ -87,96 +159,274 @@ impl Scanner {
     fn make_token(&self, token: TokenType) -> Token {
         Token::new(token,
                 self.contents[self.start..self.current].to_string(), 
-                self.line)
+                self.line,
+                self.span())
     }
 
     fn make_error_token(&self, message: String) -> Token {
         Token::new(TokenType::Error,
                 message, 
-                self.line)
+                self.line,
+                self.span())
+    }
+
+    fn span(&self) -> Span {
+        Span { start: self.start, end: self.current }
     }
 
     fn advance(&mut self) -> char {
         let c = self.peek();
-        self.current += 1;
+        self.current += c.len_utf8();
         c
     }
 
-    fn skip_whitespace(&mut self) {
+    // Skips whitespace and comments, recording them in `trivia` when the
+    // scanner keeps trivia. Trailing trivia stops at the end of the line and
+    // leaves an unterminated comment to be reported with the next token.
+    fn skip_whitespace(&mut self, trivia: &mut Vec<Trivia>,
+                       trailing: bool) -> Result<(), Token> {
         loop {
-            if self.is_end() { return };
-            let c = self.peek();
-            match c {
-                ' ' | '\t' | '\r' => { self.advance(); },
+            if self.is_end() { return Ok(()) };
+            let start = self.current;
+            let line = self.line;
+
+            let kind = match self.peek() {
+                ' ' | '\t' | '\r' => {
+                    while !self.is_end() && matches!(self.peek(), ' ' | '\t' | '\r') {
+                        self.advance();
+                    }
+                    TriviaKind::Whitespace
+                },
+                '\n' if trailing => return Ok(()),
                 '\n' => {
                     self.line += 1;
                     self.advance();
+                    TriviaKind::Newline
                 }
-                '/' => {
-                    if self.can_peek_next() && self.peek_next() == '/' {
-                        while !self.is_end() && self.peek() != '\n' {
-                            self.advance();
+                '/' if self.can_peek_next() && self.peek_next() == '/' => {
+                    while !self.is_end() && self.peek() != '\n' {
+                        self.advance();
+                    }
+                    TriviaKind::LineComment
+                }
+                '/' if self.can_peek_next() && self.peek_next() == '*' => {
+                    if !self.block_comment() {
+                        if trailing {
+                            self.current = start;
+                            self.line = line;
+                            return Ok(());
                         }
+                        self.start = start;
+                        return Err(self.make_error_token(String::from("Unterminated comment")));
                     }
-                    return;
+                    TriviaKind::BlockComment
                 }
-                _ => return
+                _ => return Ok(())
+            };
+
+            if self.trivia {
+                trivia.push(Trivia { kind, text: self.contents[start..self.current].to_string() });
             }
         }
     }
 
+    // Block comments nest, so every `/*` needs its own `*/`.
+    fn block_comment(&mut self) -> bool {
+        let mut depth = 0;
+
+        while !self.is_end() {
+            if self.contents[self.current..].starts_with("/*") {
+                self.current += 2;
+                depth += 1;
+            } else if self.contents[self.current..].starts_with("*/") {
+                self.current += 2;
+                depth -= 1;
+                if depth == 0 { return true }
+            } else if self.advance() == '\n' {
+                self.line += 1;
+            }
+        }
+        false
+    }
+
     fn matches(&mut self, expected: char) -> bool {
         if self.is_end() { return false };
         if self.peek() != expected { return false }
 
-        self.current += 1;
+        self.current += expected.len_utf8();
         true
     }
 
     fn is_end(&self) -> bool {
-        self.current == self.contents.len()
+        self.current >= self.contents.len()
     }
 
     fn can_peek_next(&self) -> bool {
-        !self.is_end() && self.current != self.contents.len() - 1
+        !self.is_end() && self.current + self.peek().len_utf8() < self.contents.len()
     }
 
+    // `start` and `current` are byte offsets into `contents`, always kept on
+    // char boundaries so that slicing a lexeme never splits a code point.
+    fn char_at(&self, offset: usize) -> char {
+        self.contents[offset..].chars().next().unwrap_or('\0')
+    }
 
     fn peek(&self) -> char {
-        self.contents.as_bytes()[self.current] as char
+        self.char_at(self.current)
     }
 
     fn peek_next(&self) -> char {
-        self.contents.as_bytes()[self.current + 1] as char
+        self.contents[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn string(&mut self) -> Token {
+        if self.peek() == '"' && self.peek_next() == '"' {
+            self.advance();
+            self.advance();
+            return self.raw_string();
+        }
+
+        self.string_part()
+    }
+
+    // Scans up to the closing quote or the next `${`. Parts after an
+    // interpolated expression start at its closing `}`.
+    fn string_part(&mut self) -> Token {
+        // Keep scanning up to the closing quote after a bad escape so the
+        // rest of the literal is not mistaken for code.
+        let mut error = None;
+        let mut token_type = TokenType::String;
+
         while !self.is_end() && self.peek() != '"' {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.peek() == '$' && self.peek_next() == '{' {
+                self.advance();
+                self.advance();
+                self.interpolation.push(0);
+                token_type = TokenType::Interpolation;
+                break;
+            }
+
+            match self.advance() {
+                '\n' => self.line += 1,
+                '\\' if !self.escape() && error.is_none() => {
+                    error = Some(String::from("Invalid escape sequence"));
+                }
+                _ => {}
             }
+        }
+
+        if token_type == TokenType::String {
+            if self.is_end() { return self.make_error_token(String::from("Unterminated String")) }
             self.advance();
         }
 
+        match error {
+            Some(message) => self.make_error_token(message),
+            None => self.make_token(token_type)
+        }
+    }
+
+    // A triple-quoted string is taken verbatim, escapes and newlines included.
+    fn raw_string(&mut self) -> Token {
+        while !self.is_end() && !self.contents[self.current..].starts_with("\"\"\"") {
+            if self.advance() == '\n' {
+                self.line += 1;
+            }
+        }
+
         if self.is_end() { return self.make_error_token(String::from("Unterminated String")) }
-        self.advance();
+        self.current += 3;
         self.make_token(TokenType::String)
     }
 
+    // Consumes the escape following a backslash and reports whether it is
+    // one the compiler knows how to decode.
+    fn escape(&mut self) -> bool {
+        if self.is_end() || self.peek() == '\n' { return false }
+
+        match self.advance() {
+            'n' | 't' | 'r' | '0' | '"' | '\\' | '$' => true,
+            'u' => {
+                if !self.matches('{') { return false }
+
+                let digits = self.current;
+                while !self.is_end() && self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let hex = &self.contents[digits..self.current];
+                let valid = !hex.is_empty() && hex.len() <= 6 &&
+                            u32::from_str_radix(hex, 16).ok()
+                                .and_then(std::char::from_u32)
+                                .is_some();
+
+                self.matches('}') && valid
+            }
+            _ => false
+        }
+    }
+
     fn number(&mut self) -> Token {
-        while !self.is_end() && is_digit(self.peek()) {
+        let radix = match (self.char_at(self.start), self.peek()) {
+            ('0', 'x') | ('0', 'X') => 16,
+            ('0', 'b') | ('0', 'B') => 2,
+            _ => 10
+        };
+
+        let mut valid = if radix == 10 {
+            self.digits(radix);
+            self.fraction() && self.exponent()
+        } else {
             self.advance();
-        }
+            self.digits(radix)
+        };
 
-        if !self.is_end() && self.peek() == '.' {
+        // Swallow anything glued to the literal so that `0x1g` or `1_` is
+        // reported as one malformed number.
+        while !self.is_end() && is_alphanumeric(self.peek()) {
             self.advance();
+            valid = false;
+        }
 
-            while !self.is_end() && is_digit(self.peek()) {
-                self.advance();
+        if !valid { return self.make_error_token(String::from("Malformed number")) }
+        self.make_token(TokenType::Number)
+    }
+
+    // Consumes digits in the given radix, allowing single `_` separators
+    // between them, and reports whether there was at least one digit.
+    fn digits(&mut self, radix: u32) -> bool {
+        let mut count = 0;
+
+        while !self.is_end() {
+            let c = self.peek();
+            let after_digit = self.contents[..self.current].chars()
+                                  .next_back()
+                                  .is_some_and(|last| last.is_digit(radix));
+
+            if c.is_digit(radix) {
+                count += 1;
+            } else if !(c == '_' && after_digit && self.peek_next().is_digit(radix)) {
+                break;
             }
+            self.advance();
+        }
+        count > 0
+    }
+
+    fn fraction(&mut self) -> bool {
+        if self.peek() != '.' { return true }
+
+        self.advance();
+        self.digits(10)
+    }
 
+    fn exponent(&mut self) -> bool {
+        if self.peek() != 'e' && self.peek() != 'E' { return true }
+
+        self.advance();
+        if self.peek() == '+' || self.peek() == '-' {
+            self.advance();
         }
-        self.make_token(TokenType::Number)
+        self.digits(10)
     }
 
     fn identifier(&mut self) -> Token {
@@ -187,7 +437,7 @@ impl Scanner {
     }
 
     fn identifier_type(&self) -> TokenType {
-        let c0 = self.contents.as_bytes()[self.start] as char;
+        let c0 = self.char_at(self.start);
 
         match c0 {
             'a' => self.check_keyword(self.start + 1, 2, "ar", TokenType::And),
@@ -203,7 +453,7 @@ impl Scanner {
             'w' => self.check_keyword(self.start + 1, 4, "hile", TokenType::While),
             'f' => {
                 if self.current - self.start > 1 {
-                    let c1 = self.contents.as_bytes()[self.start + 1] as char;
+                    let c1 = self.char_at(self.start + 1);
 
                     match c1 {
                         'a' => self.check_keyword(self.start + 2, 3, "lse", TokenType::False),
@@ -217,7 +467,7 @@ impl Scanner {
             }
             't' => {
                 if self.current - self.start > 1 {
-                    let c1 = self.contents.as_bytes()[self.start + 1] as char;
+                    let c1 = self.char_at(self.start + 1);
 
                     match c1 {
                         'h' => self.check_keyword(self.start + 2, 2, "is", TokenType::This),
@@ -242,45 +492,43 @@ impl Scanner {
         TokenType::Identifier
     }
 
-}
-
-fn is_alphanumeric(c: char) -> bool {
-    is_alpha(c) || is_digit(c)
-}
-
-fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
-}
-
-fn is_alpha(c: char) -> bool {
-    (c >= 'A' && c <= 'Z') ||
-    (c >= 'a' && c <= 'z') ||
-    c == '_'
-}
-
-impl Iterator for Scanner {
-    type Item = Token;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.skip_whitespace();
-        self.start = self.current;
-
-        if self.is_end() { return None }
-
+    fn scan_token(&mut self) -> Token {
         let c = self.advance();
 
         match c {
-            '(' => return Some(self.make_token(TokenType::LeftParen)),
-            ')' => return Some(self.make_token(TokenType::RightParen)),
-            '{' => return Some(self.make_token(TokenType::LeftBrace)),
-            '}' => return Some(self.make_token(TokenType::RightBrace)),
-            ';' => return Some(self.make_token(TokenType::Semicolon)),
-            ',' => return Some(self.make_token(TokenType::Comma)),
-            '.' => return Some(self.make_token(TokenType::Dot)),
-            '-' => return Some(self.make_token(TokenType::Minus)),
-            '+' => return Some(self.make_token(TokenType::Plus)),
-            '/' => return Some(self.make_token(TokenType::Slash)),
-            '*' => return Some(self.make_token(TokenType::Star)),
+            '(' => return self.make_token(TokenType::LeftParen),
+            ')' => return self.make_token(TokenType::RightParen),
+            '{' => {
+                if let Some(depth) = self.interpolation.last_mut() {
+                    *depth += 1;
+                }
+                return self.make_token(TokenType::LeftBrace)
+            },
+            '}' => {
+                match self.interpolation.last_mut() {
+                    Some(0) => {
+                        self.interpolation.pop();
+                        return self.string_part()
+                    }
+                    Some(depth) => *depth -= 1,
+                    None => {}
+                }
+                return self.make_token(TokenType::RightBrace)
+            },
+            ';' => return self.make_token(TokenType::Semicolon),
+            ',' => return self.make_token(TokenType::Comma),
+            '.' => return self.make_token(TokenType::Dot),
+            '-' => return self.make_token(TokenType::Minus),
+            '+' => return self.make_token(TokenType::Plus),
+            '/' => return self.make_token(TokenType::Slash),
+            '*' if self.matches('*') => return self.make_token(TokenType::StarStar),
+            '*' => return self.make_token(TokenType::Star),
+            '%' => return self.make_token(TokenType::Percent),
+            '&' => return self.make_token(TokenType::Ampersand),
+            '|' => return self.make_token(TokenType::Pipe),
+            '^' => return self.make_token(TokenType::Caret),
+            '~' if self.matches('/') => return self.make_token(TokenType::TildeSlash),
+            '~' => return self.make_token(TokenType::Tilde),
 
             '!' => {
                     let token = if self.matches('=') {
@@ -288,7 +536,7 @@ impl Iterator for Scanner {
                     } else { 
                         TokenType::Bang 
                     };
-                    return Some(self.make_token(token))
+                    return self.make_token(token)
             },
             '=' => {
                     let token = if self.matches('=') {
@@ -296,34 +544,127 @@ impl Iterator for Scanner {
                     } else { 
                         TokenType::Equal
                     };
-                    return Some(self.make_token(token))
+                    return self.make_token(token)
             },
             '<' => {
                     let token = if self.matches('=') {
                         TokenType::LessEqual
+                    } else if self.matches('<') {
+                        TokenType::LessLess
                     } else { 
                         TokenType::Less 
                     };
-                    return Some(self.make_token(token))
+                    return self.make_token(token)
             },
             '>' => {
                     let token = if self.matches('=') {
                         TokenType::GreaterEqual
+                    } else if self.matches('>') {
+                        TokenType::GreaterGreater
                     } else { 
                         TokenType::Greater
                     };
-                    return Some(self.make_token(token))
+                    return self.make_token(token)
             },
 
-            '"' => return Some(self.string()),
-            '0'..='9' => return Some(self.number()),
-            'a'..='z' | 'A'..='Z' => return Some(self.identifier()),
+            '"' => return self.string(),
+            '0'..='9' => return self.number(),
+            c if is_alpha(c) => return self.identifier(),
             _ => {}
         }
 
-        Some(self.make_error_token(String::from("unexpected character")))
+        self.make_error_token(String::from("unexpected character"))
     }
 }
 
+fn is_alphanumeric(c: char) -> bool {
+    is_alpha(c) || is_digit(c)
+}
+
+fn is_digit(c: char) -> bool {
+    c.is_ascii_digit()
+}
+
+// Identifiers may use any Unicode letter, which approximates XID_Start and
+// XID_Continue without pulling in a dependency.
+fn is_alpha(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+impl Iterator for Scanner {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut leading_trivia = Vec::new();
+        if let Err(mut error) = self.skip_whitespace(&mut leading_trivia, false) {
+            error.leading_trivia = leading_trivia;
+            return Some(error);
+        }
+        self.start = self.current;
+
+        if self.is_end() {
+            if !self.trivia || self.eof_emitted { return None }
+
+            self.eof_emitted = true;
+            let mut token = self.make_token(TokenType::EOF);
+            token.leading_trivia = leading_trivia;
+            return Some(token);
+        }
+
+        let mut token = self.scan_token();
+        token.leading_trivia = leading_trivia;
+
+        if self.trivia {
+            // Scanning trailing trivia never fails, an unterminated comment
+            // is left to be reported with the next token.
+            let _ = self.skip_whitespace(&mut token.trailing_trivia, true);
+        }
+        Some(token)
+    }
+}
+
+/// Decodes the escape sequences in the body of a string literal. Bodies of
+/// triple-quoted literals are raw and must not be passed through here.
+pub fn unescape(body: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(body.len());
+    let mut chars = body.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        let decoded = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('$') => '$',
+            Some('u') => {
+                let rest = chars.as_str();
+                let end = rest.find('}')
+                              .filter(|_| rest.starts_with('{'))
+                              .ok_or_else(|| String::from("Invalid unicode escape."))?;
+                let hex = &rest[1..end];
+                let code = Some(hex)
+                               .filter(|hex| !hex.is_empty() && hex.len() <= 6)
+                               .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                               .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                               .and_then(std::char::from_u32)
+                               .ok_or_else(|| String::from("Invalid unicode escape."))?;
+                chars = rest[end + 1..].chars();
+                code
+            }
+            _ => return Err(String::from("Invalid escape sequence."))
+        };
+        result.push(decoded);
+    }
+
+    Ok(result)
+}
+
 #[cfg(test)]
 mod test;
@@ -1,4 +1,4 @@
-use super::{Scanner, TokenType};
+use super::{unescape, Scanner, TokenType};
 
 #[test]
 fn expressions() {
@@ -19,6 +19,29 @@ fn expressions() {
     assert_eq!(scanner.next().unwrap().token_type, TokenType::RightParen);
 }
 
+#[test]
+fn integer_operators() {
+    let mut scanner = Scanner::new(String::from("a % b ~/ c"));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Identifier);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Percent);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Identifier);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::TildeSlash);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Identifier);
+}
+
+#[test]
+fn bitwise_operators() {
+    let mut scanner = Scanner::new(String::from("~a & b | c ^ d << 2 >> 1 ** 3 * 4 < 5 > 6"));
+    let types: Vec<TokenType> = scanner.by_ref().map(|token| token.token_type).collect();
+    assert_eq!(types, [
+        TokenType::Tilde, TokenType::Identifier, TokenType::Ampersand, TokenType::Identifier,
+        TokenType::Pipe, TokenType::Identifier, TokenType::Caret, TokenType::Identifier,
+        TokenType::LessLess, TokenType::Number, TokenType::GreaterGreater, TokenType::Number,
+        TokenType::StarStar, TokenType::Number, TokenType::Star, TokenType::Number,
+        TokenType::Less, TokenType::Number, TokenType::Greater, TokenType::Number
+    ]);
+}
+
 #[test]
 fn identifier() {
     let mut scanner = Scanner::new(String::from("var dsf23 = 3;"));
@@ -110,3 +133,171 @@ fn keyword_this() {
     assert_eq!(scanner.next().unwrap().token_type, TokenType::RightBrace);
     assert_eq!(scanner.next().unwrap().token_type, TokenType::Identifier);
 }
+
+#[test]
+fn unicode_string() {
+    let mut scanner = Scanner::new(String::from("\"héllo wörld ✓\" + 1"));
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::String);
+    assert_eq!(token.lexeme, "\"héllo wörld ✓\"");
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Plus);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+}
+
+#[test]
+fn unicode_comment() {
+    let mut scanner = Scanner::new(String::from("// ¿qué? ✓\n1"));
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::Number);
+    assert_eq!(token.line, 2);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn unexpected_multibyte_character() {
+    let mut scanner = Scanner::new(String::from("1 ✓ 2"));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Error);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn unicode_identifier() {
+    let mut scanner = Scanner::new(String::from("var größe = _π2;"));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Var);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::Identifier);
+    assert_eq!(token.lexeme, "größe");
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Equal);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::Identifier);
+    assert_eq!(token.lexeme, "_π2");
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Semicolon);
+}
+
+#[test]
+fn string_escapes() {
+    let mut scanner = Scanner::new(String::from(r#""a\n\t\"\\\u{1F600}" "bad\q" + "\u{110000}""#));
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::String);
+    assert_eq!(token.lexeme, r#""a\n\t\"\\\u{1F600}""#);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Error);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Plus);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Error);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn raw_string() {
+    let mut scanner = Scanner::new(String::from("\"\"\"line \\d+\n\"quoted\" \"\"\" 1"));
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::String);
+    assert_eq!(token.lexeme, "\"\"\"line \\d+\n\"quoted\" \"\"\"");
+    assert_eq!(token.line, 2);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn unterminated_raw_string() {
+    let mut scanner = Scanner::new(String::from("\"\"\"never closed\"\""));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Error);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn unescape_sequences() {
+    assert_eq!(unescape(r#"a\n\t\r\0\"\\"#).unwrap(), "a\n\t\r\0\"\\");
+    assert_eq!(unescape(r"\u{48}\u{e9}\u{1F600}!").unwrap(), "Hé😀!");
+    assert!(unescape(r"\q").is_err());
+    assert!(unescape(r"\u{}").is_err());
+    assert!(unescape(r"\u{D800}").is_err());
+    assert!(unescape(r"\u{41").is_err());
+}
+
+#[test]
+fn interpolation() {
+    let mut scanner = Scanner::new(String::from(r#""Hello ${name}, you have ${count + 1} messages""#));
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::Interpolation);
+    assert_eq!(token.lexeme, r#""Hello ${"#);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Identifier);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::Interpolation);
+    assert_eq!(token.lexeme, "}, you have ${");
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Identifier);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Plus);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::String);
+    assert_eq!(token.lexeme, r#"} messages""#);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn nested_interpolation() {
+    let mut scanner = Scanner::new(String::from(r#""a ${ {} "b ${c}" } d""#));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Interpolation);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::LeftBrace);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::RightBrace);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Interpolation);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Identifier);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::String);
+    assert_eq!(token.lexeme, r#"}""#);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::String);
+    assert_eq!(token.lexeme, r#"} d""#);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn escaped_interpolation() {
+    let mut scanner = Scanner::new(String::from(r#""cost: \${price}""#));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::String);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn number_literals() {
+    let mut scanner = Scanner::new(String::from("0xFF_FF 0b1010 1_000.5 1.5e-3 2E+8 7e2"));
+    for lexeme in &["0xFF_FF", "0b1010", "1_000.5", "1.5e-3", "2E+8", "7e2"] {
+        let token = scanner.next().unwrap();
+        assert_eq!(token.token_type, TokenType::Number);
+        assert_eq!(&token.lexeme, lexeme);
+    }
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn malformed_numbers() {
+    for source in &["1.", "0x", "0b", "0b102", "0xFg", "1_", "1__0", "1e", "1e+", "3abc", "1._5"] {
+        let mut scanner = Scanner::new(String::from(*source));
+        let token = scanner.next().unwrap();
+        assert_eq!(token.token_type, TokenType::Error, "{}", source);
+        assert!(scanner.next().is_none(), "{}", source);
+    }
+}
+
+#[test]
+fn block_comments() {
+    let mut scanner = Scanner::new(String::from("1 /* outer /* inner\n */ still\n comment */ + /**/ 2"));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::Plus);
+    assert_eq!(token.line, 3);
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+    assert!(scanner.next().is_none());
+}
+
+#[test]
+fn unterminated_block_comment() {
+    let mut scanner = Scanner::new(String::from("1 /* outer /* inner */\n"));
+    assert_eq!(scanner.next().unwrap().token_type, TokenType::Number);
+    let token = scanner.next().unwrap();
+    assert_eq!(token.token_type, TokenType::Error);
+    assert_eq!(token.lexeme, "Unterminated comment");
+    assert_eq!(token.line, 2);
+    assert!(scanner.next().is_none());
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::scanner::*;
+
+/// A node of the concrete syntax tree. Tokens between matching parentheses
+/// or braces are grouped under their delimiters; a closing delimiter with no
+/// opener is kept as a plain token and an unclosed group has no `close`.
+#[derive(Debug)]
+#[derive(Clone)]
+pub enum Node {
+    Token(Token),
+    Group {
+        open: Token,
+        children: Vec<Node>,
+        close: Option<Token>
+    }
+}
+
+/// Lossless syntax tree of a source file. Every byte of the source lives in
+/// either a token or its trivia, so displaying the tree gives back the
+/// source exactly.
+#[derive(Debug)]
+pub struct SyntaxTree {
+    source: String,
+    nodes: Vec<Node>
+}
+
+impl SyntaxTree {
+    pub fn parse(source: String) -> Self {
+        let mut scanner = Scanner::with_trivia(source.clone());
+        let nodes = group(&mut scanner, None).0;
+
+        Self {
+            source,
+            nodes
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// All tokens in source order, ending with the EOF token.
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = Vec::new();
+        collect_tokens(&self.nodes, &mut tokens);
+        tokens
+    }
+
+    /// Source text of a token, which for error tokens differs from the
+    /// lexeme holding the message.
+    pub fn text(&self, token: &Token) -> &str {
+        &self.source[token.span.start..token.span.end]
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in self.tokens() {
+            for trivia in &token.leading_trivia {
+                f.write_str(&trivia.text)?;
+            }
+            f.write_str(self.text(token))?;
+            for trivia in &token.trailing_trivia {
+                f.write_str(&trivia.text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Collects nodes until the closing delimiter of the enclosing group, which
+// is returned alongside them, or until the scanner runs out.
+fn group(scanner: &mut Scanner, closer: Option<TokenType>) -> (Vec<Node>, Option<Token>) {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = scanner.next() {
+        if Some(token.token_type) == closer {
+            return (nodes, Some(token));
+        }
+
+        let closing = match token.token_type {
+            TokenType::LeftParen => TokenType::RightParen,
+            TokenType::LeftBrace => TokenType::RightBrace,
+            _ => {
+                nodes.push(Node::Token(token));
+                continue;
+            }
+        };
+
+        let (children, close) = group(scanner, Some(closing));
+        // An unclosed group swallowed the rest of the file, EOF included.
+        nodes.push(Node::Group { open: token, children, close });
+    }
+    (nodes, None)
+}
+
+fn collect_tokens<'a>(nodes: &'a [Node], tokens: &mut Vec<&'a Token>) {
+    for node in nodes {
+        match node {
+            Node::Token(token) => tokens.push(token),
+            Node::Group { open, children, close } => {
+                tokens.push(open);
+                collect_tokens(children, tokens);
+                if let Some(close) = close {
+                    tokens.push(close);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
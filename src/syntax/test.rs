@@ -0,0 +1,58 @@
+use super::{Node, SyntaxTree};
+use crate::scanner::{Scanner, TokenType, TriviaKind};
+
+fn round_trip(source: &str) {
+    let tree = SyntaxTree::parse(String::from(source));
+    assert_eq!(tree.to_string(), source);
+}
+
+#[test]
+fn round_trips_source() {
+    round_trip("");
+    round_trip("   \n\n");
+    round_trip("(-1 + 2) * 3 - -4");
+    round_trip("// header\nclass Maya {\n    fun hey() { return 5; } // five\n}\n\n/* tail /* nested */ */\n");
+    round_trip("var s = \"tab\\t ${ {a} + \"${b}\" } done\";\r\n\"\"\"raw\n\"\"\"");
+}
+
+#[test]
+fn round_trips_broken_source() {
+    round_trip("1 + 0x + \"open");
+    round_trip("{ ( } ) ✓ )");
+    round_trip("a /* never closed\n");
+    round_trip("a = 1; /* never closed");
+}
+
+#[test]
+fn attaches_trivia() {
+    let tokens: Vec<_> = Scanner::with_trivia(String::from("// lead\n  a /* b */ // c\n")).collect();
+
+    assert_eq!(tokens.len(), 2);
+    let kinds: Vec<_> = tokens[0].leading_trivia.iter().map(|t| t.kind).collect();
+    assert_eq!(kinds, [TriviaKind::LineComment, TriviaKind::Newline, TriviaKind::Whitespace]);
+    let kinds: Vec<_> = tokens[0].trailing_trivia.iter().map(|t| t.kind).collect();
+    assert_eq!(kinds, [TriviaKind::Whitespace, TriviaKind::BlockComment,
+                       TriviaKind::Whitespace, TriviaKind::LineComment]);
+
+    assert_eq!(tokens[1].token_type, TokenType::EOF);
+    assert_eq!(tokens[1].leading_trivia[0].kind, TriviaKind::Newline);
+}
+
+#[test]
+fn groups_delimiters() {
+    let tree = SyntaxTree::parse(String::from("f(a, {b}) }"));
+    let nodes = tree.nodes();
+
+    assert_eq!(nodes.len(), 4);
+    match &nodes[1] {
+        Node::Group { open, children, close } => {
+            assert_eq!(open.token_type, TokenType::LeftParen);
+            assert_eq!(children.len(), 3);
+            assert!(matches!(children[2], Node::Group { .. }));
+            assert_eq!(close.as_ref().unwrap().token_type, TokenType::RightParen);
+        }
+        node => panic!("expected a group, got {:?}", node)
+    }
+    assert!(matches!(&nodes[2], Node::Token(token) if token.token_type == TokenType::RightBrace));
+    assert!(matches!(&nodes[3], Node::Token(token) if token.token_type == TokenType::EOF));
+}
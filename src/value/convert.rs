@@ -0,0 +1,194 @@
+use std::rc::Rc;
+
+use super::*;
+
+/// A Rust type a script value can be read as, for typed native functions.
+pub trait FromLox: Sized {
+    /// What a matching value looks like, as in "a number".
+    fn expected() -> String;
+
+    fn from_lox(value: &Value) -> Option<Self>;
+}
+
+/// A Rust type that can be handed to scripts as a value.
+pub trait IntoLox {
+    fn into_lox(self) -> Value;
+}
+
+impl FromLox for Value {
+    fn expected() -> String {
+        String::from("a value")
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl IntoLox for Value {
+    fn into_lox(self) -> Value {
+        self
+    }
+}
+
+impl FromLox for f64 {
+    fn expected() -> String {
+        String::from("a number")
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => Some(*number),
+            Value::Int(int) => Some(*int as f64),
+            _ => None
+        }
+    }
+}
+
+impl IntoLox for f64 {
+    fn into_lox(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl FromLox for i64 {
+    fn expected() -> String {
+        String::from("an integer")
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        as_integer(value)
+    }
+}
+
+impl IntoLox for i64 {
+    fn into_lox(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl FromLox for bool {
+    fn expected() -> String {
+        String::from("a boolean")
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(boolean) => Some(*boolean),
+            _ => None
+        }
+    }
+}
+
+impl IntoLox for bool {
+    fn into_lox(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl FromLox for String {
+    fn expected() -> String {
+        String::from("a string")
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(string) => Some(string.to_string()),
+            _ => None
+        }
+    }
+}
+
+impl IntoLox for String {
+    fn into_lox(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoLox for &str {
+    fn into_lox(self) -> Value {
+        Value::String(Rc::from(self))
+    }
+}
+
+impl IntoLox for () {
+    fn into_lox(self) -> Value {
+        Value::Nil
+    }
+}
+
+// `nil` stands for `None`.
+impl<T: FromLox> FromLox for Option<T> {
+    fn expected() -> String {
+        format!("{} or nil", T::expected())
+    }
+
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Nil => Some(None),
+            value => T::from_lox(value).map(Some)
+        }
+    }
+}
+
+impl<T: IntoLox> IntoLox for Option<T> {
+    fn into_lox(self) -> Value {
+        match self {
+            Some(value) => value.into_lox(),
+            None => Value::Nil
+        }
+    }
+}
+
+/// The arguments of a typed native function, as a tuple of `FromLox`
+/// types whose length is the function's arity.
+pub trait FromLoxArgs: Sized {
+    const ARITY: usize;
+
+    fn from_lox_args(args: &[Value]) -> Result<Self, String>;
+}
+
+macro_rules! impl_from_lox_args {
+    ($arity:expr; $($arg:ident $index:tt),*) => {
+        impl<$($arg: FromLox),*> FromLoxArgs for ($($arg,)*) {
+            const ARITY: usize = $arity;
+
+            #[allow(unused_variables)]
+            fn from_lox_args(args: &[Value]) -> Result<Self, String> {
+                Ok(($(argument::<$arg>(args, $index)?,)*))
+            }
+        }
+    };
+}
+
+impl_from_lox_args!(0;);
+impl_from_lox_args!(1; A 0);
+impl_from_lox_args!(2; A 0, B 1);
+impl_from_lox_args!(3; A 0, B 1, C 2);
+impl_from_lox_args!(4; A 0, B 1, C 2, D 3);
+impl_from_lox_args!(5; A 0, B 1, C 2, D 3, E 4);
+
+fn argument<T: FromLox>(args: &[Value], index: usize) -> Result<T, String> {
+    T::from_lox(&args[index]).ok_or_else(|| {
+        format!("Expected {} for argument {} but got {}.",
+                T::expected(), index + 1, type_name(&args[index]))
+    })
+}
+
+/// What a typed native function may return: any `IntoLox` value, or a
+/// `Result` whose error becomes a runtime error in the script.
+pub trait NativeResult {
+    fn into_result(self) -> Result<Value, String>;
+}
+
+impl<T: IntoLox> NativeResult for T {
+    fn into_result(self) -> Result<Value, String> {
+        Ok(self.into_lox())
+    }
+}
+
+impl<T: IntoLox> NativeResult for Result<T, String> {
+    fn into_result(self) -> Result<Value, String> {
+        self.map(IntoLox::into_lox)
+    }
+}
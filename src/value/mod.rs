@@ -1,5 +1,227 @@
-pub type Value = f32;
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
 
-pub fn print_value(value: Value) {
-    print!("'{}'", value);
+use crate::chunk::OpCode;
+use crate::vm::VM;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Int(i64),
+    String(Rc<str>),
+    Bool(bool),
+    Nil,
+    Native(Rc<NativeFunction>),
+    Userdata(Rc<userdata::Userdata>)
+}
+
+/// Signature of a host function callable from scripts. It gets the VM that
+/// called it and exactly as many arguments as its arity, and an error it
+/// returns becomes a runtime error in the script.
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+/// A native's body, which may also be a closure over host state.
+pub type NativeBody = dyn Fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: Box<NativeBody>
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// Natives are equal only to themselves.
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Formats values the way Lox prints them: numbers in their shortest
+/// round-tripping form, with `.0` after integral floats to tell them from
+/// integers, and `nan`, `inf` and `-inf` for the special values. Floats of
+/// 1e21 and up, or below 1e-6, switch to exponent notation such as `1e300`
+/// and `1.5e-7`, as `%g` does in clox.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(number) if number.is_nan() => f.write_str("nan"),
+            Value::Number(number) if number.is_finite() && *number != 0.0 &&
+                                     (number.abs() >= 1e21 || number.abs() < 1e-6) => write!(f, "{:e}", number),
+            Value::Number(number) if number.is_finite() && number.fract() == 0.0 => write!(f, "{}.0", number),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Int(int) => write!(f, "{}", int),
+            Value::String(string) => f.write_str(string),
+            Value::Bool(boolean) => write!(f, "{}", boolean),
+            Value::Nil => f.write_str("nil"),
+            Value::Native(_) => f.write_str("<native fn>"),
+            Value::Userdata(userdata) => write!(f, "{} instance", userdata.class.name)
+        }
+    }
+}
+
+/// Names the kind of a value, for error messages.
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) => "a number",
+        Value::Int(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Bool(_) => "a boolean",
+        Value::Nil => "nil",
+        Value::Native(_) => "a function",
+        Value::Userdata(_) => "an object"
+    }
+}
+
+/// Applies an arithmetic instruction to two operands, or returns the
+/// runtime error that doing so raises. Two integers give an integer, except
+/// under `/` and `**` with a negative exponent, and an integer meeting a
+/// float is promoted to a float.
+pub fn arithmetic(op: OpCode, left: &Value, right: &Value) -> Result<Value, String> {
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => integer_arithmetic(op, *left, *right),
+        (Value::String(left), Value::String(right)) if op == OpCode::Add => {
+            Ok(Value::String(format!("{}{}", left, right).into()))
+        }
+        _ => match (as_float(left), as_float(right)) {
+            (Some(left), Some(right)) => Ok(Value::Number(float_arithmetic(op, left, right))),
+            _ if op == OpCode::Add => Err(String::from("Operands must be two numbers or two strings.")),
+            _ => Err(String::from("Operands must be numbers."))
+        }
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => Some(*number),
+        Value::Int(int) => Some(*int as f64),
+        _ => None
+    }
 }
+
+// `~/` rounds towards negative infinity, and `%` takes the sign of the
+// divisor to match, so that `a == (a ~/ b) * b + a % b`.
+fn integer_arithmetic(op: OpCode, left: i64, right: i64) -> Result<Value, String> {
+    if right == 0 && (op == OpCode::Modulo || op == OpCode::IntDivide) {
+        return Err(String::from("Division by zero."));
+    }
+
+    let result = match op {
+        OpCode::Add => left.checked_add(right),
+        OpCode::Subtract => left.checked_sub(right),
+        OpCode::Multiply => left.checked_mul(right),
+        OpCode::Divide => return Ok(Value::Number(left as f64 / right as f64)),
+        OpCode::Power if right < 0 => return Ok(Value::Number((left as f64).powf(right as f64))),
+        OpCode::Power => match u32::try_from(right) {
+            Ok(exponent) => left.checked_pow(exponent),
+            // Only 0, 1 and -1 survive exponents this large.
+            Err(_) => match left {
+                0 | 1 => Some(left),
+                -1 => Some(if right % 2 == 0 { 1 } else { -1 }),
+                _ => None
+            }
+        },
+        OpCode::Modulo => {
+            let remainder = left.wrapping_rem(right);
+            if remainder != 0 && (remainder < 0) != (right < 0) {
+                Some(remainder + right)
+            } else {
+                Some(remainder)
+            }
+        }
+        OpCode::IntDivide => left.checked_div(right).map(|quotient| {
+            if left % right != 0 && (left < 0) != (right < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        }),
+        _ => None
+    };
+    result.map(Value::Int).ok_or_else(|| String::from("Integer overflow."))
+}
+
+pub fn float_arithmetic(op: OpCode, left: f64, right: f64) -> f64 {
+    match op {
+        OpCode::Add => left + right,
+        OpCode::Subtract => left - right,
+        OpCode::Multiply => left * right,
+        OpCode::Divide => left / right,
+        OpCode::Modulo => {
+            let remainder = left % right;
+            if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+                // A tiny remainder can round up to the divisor when moved
+                // across; taking it modulo again brings that back to zero.
+                (remainder + right) % right
+            } else {
+                remainder
+            }
+        }
+        OpCode::IntDivide => (left / right).floor(),
+        OpCode::Power => left.powf(right),
+        _ => 0.0
+    }
+}
+
+/// Applies a bitwise or shift instruction to two integer-valued operands.
+/// Floats are accepted when they hold a whole number, and the result is
+/// always an integer. `<<` drops the bits shifted past the top, as the
+/// fixed-width code it is used to port expects, and `>>` keeps the sign.
+pub fn bitwise(op: OpCode, left: &Value, right: &Value) -> Result<Value, String> {
+    let (left, right) = match (as_integer(left), as_integer(right)) {
+        (Some(left), Some(right)) => (left, right),
+        _ => return Err(String::from("Operands must be integers."))
+    };
+
+    let result = match op {
+        OpCode::BitAnd => left & right,
+        OpCode::BitOr => left | right,
+        OpCode::BitXor => left ^ right,
+        OpCode::ShiftLeft | OpCode::ShiftRight if right < 0 => {
+            return Err(String::from("Shift amount must not be negative."))
+        }
+        OpCode::ShiftLeft => left.checked_shl(right.min(64) as u32).unwrap_or(0),
+        OpCode::ShiftRight => left >> right.min(63),
+        _ => 0
+    };
+    Ok(Value::Int(result))
+}
+
+pub fn complement(value: &Value) -> Result<Value, String> {
+    match as_integer(value) {
+        Some(int) => Ok(Value::Int(!int)),
+        None => Err(String::from("Operand must be an integer."))
+    }
+}
+
+pub(crate) fn as_integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(int) => Some(*int),
+        Value::Number(number) if number.fract() == 0.0 &&
+                                 *number >= i64::MIN as f64 &&
+                                 *number < i64::MAX as f64 => Some(*number as i64),
+        _ => None
+    }
+}
+
+pub fn negate(value: &Value) -> Result<Value, String> {
+    match value {
+        Value::Number(number) => Ok(Value::Number(-number)),
+        Value::Int(int) => int.checked_neg().map(Value::Int).ok_or_else(|| String::from("Integer overflow.")),
+        _ => Err(String::from("Operand must be a number."))
+    }
+}
+
+pub mod convert;
+#[cfg(feature = "nan_boxing")]
+pub mod packed;
+pub mod userdata;
+
+#[cfg(test)]
+mod test;
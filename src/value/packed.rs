@@ -0,0 +1,172 @@
+use std::mem;
+use std::rc::Rc;
+
+use super::userdata::Userdata;
+use super::*;
+
+#[cfg(not(target_pointer_width = "64"))]
+compile_error!("The nan_boxing feature needs 64-bit pointers.");
+
+// Every bit of the exponent and the top two bits of the mantissa set: a
+// quiet NaN no arithmetic produces once NaNs are made canonical.
+const QNAN: u64 = 0x7ffc_0000_0000_0000;
+const SIGN_BIT: u64 = 0x8000_0000_0000_0000;
+const OBJECT: u64 = QNAN | SIGN_BIT;
+
+// Integers that fit in 48 bits sit in the low bits under this tag.
+const INT: u64 = QNAN | 0x0001_0000_0000_0000;
+const INT_BITS: u64 = 0x0000_ffff_ffff_ffff;
+const INT_RANGE: i64 = 1 << 47;
+
+// Objects keep their pointer in the low 48 bits and their kind above it.
+const POINTER: u64 = 0x0000_ffff_ffff_ffff;
+const KIND: u64 = 0x0003_0000_0000_0000;
+const KIND_BOXED: u64 = 0;
+const KIND_NATIVE: u64 = 0x0001_0000_0000_0000;
+const KIND_USERDATA: u64 = 0x0002_0000_0000_0000;
+
+const TAG_NIL: u64 = 1;
+const TAG_FALSE: u64 = 2;
+const TAG_TRUE: u64 = 3;
+
+/// A value packed into one 64-bit word. Floats are stored as their own
+/// bits, and nil, the booleans and 48-bit integers as tagged quiet NaNs.
+/// Objects are quiet NaNs with the sign bit set, holding the pointer of a
+/// native's or userdata's own `Rc`. Strings, whose `Rc<str>` is too wide
+/// for one word, and wider integers are moved into an `Rc<Value>` instead.
+///
+/// The VM keeps these on its stack with the `nan_boxing` feature, and
+/// converts to and from `Value` at its edges.
+pub struct PackedValue(u64);
+
+impl PackedValue {
+    pub fn as_number(&self) -> Option<f64> {
+        if self.0 & QNAN != QNAN {
+            Some(f64::from_bits(self.0))
+        } else {
+            None
+        }
+    }
+
+    fn int(&self) -> Option<i64> {
+        if self.0 & (OBJECT | INT) == INT {
+            // Shift the sign bit of the 48-bit payload into place.
+            Some((((self.0 & INT_BITS) << 16) as i64) >> 16)
+        } else {
+            None
+        }
+    }
+
+    fn object(&self) -> Option<Object> {
+        if self.0 & OBJECT != OBJECT {
+            return None;
+        }
+
+        let pointer = self.0 & POINTER;
+        Some(match self.0 & KIND {
+            KIND_NATIVE => Object::Native(pointer as *const NativeFunction),
+            KIND_USERDATA => Object::Userdata(pointer as *const Userdata),
+            _ => Object::Boxed(pointer as *const Value)
+        })
+    }
+}
+
+enum Object {
+    Boxed(*const Value),
+    Native(*const NativeFunction),
+    Userdata(*const Userdata)
+}
+
+fn object_word<T: ?Sized>(pointer: *const T, kind: u64) -> PackedValue {
+    let address = pointer as *const () as u64;
+    debug_assert_eq!(address & !POINTER, 0, "pointer does not fit in 48 bits");
+    PackedValue(OBJECT | kind | address)
+}
+
+impl From<Value> for PackedValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Number(number) if number.is_nan() => PackedValue(f64::NAN.to_bits()),
+            Value::Number(number) => PackedValue(number.to_bits()),
+            Value::Int(int) if (-INT_RANGE..INT_RANGE).contains(&int) => {
+                PackedValue(INT | (int as u64 & INT_BITS))
+            }
+            Value::Nil => PackedValue(QNAN | TAG_NIL),
+            Value::Bool(false) => PackedValue(QNAN | TAG_FALSE),
+            Value::Bool(true) => PackedValue(QNAN | TAG_TRUE),
+            Value::Native(native) => object_word(Rc::into_raw(native), KIND_NATIVE),
+            Value::Userdata(userdata) => object_word(Rc::into_raw(userdata), KIND_USERDATA),
+            value => object_word(Rc::into_raw(Rc::new(value)), KIND_BOXED)
+        }
+    }
+}
+
+impl From<PackedValue> for Value {
+    fn from(packed: PackedValue) -> Self {
+        if let Some(number) = packed.as_number() {
+            return Value::Number(number);
+        }
+        if let Some(int) = packed.int() {
+            return Value::Int(int);
+        }
+
+        let object = match packed.object() {
+            Some(object) => object,
+            None => return match packed.0 & !QNAN {
+                TAG_FALSE => Value::Bool(false),
+                TAG_TRUE => Value::Bool(true),
+                _ => Value::Nil
+            }
+        };
+
+        mem::forget(packed);
+        // SAFETY: the pointer came from `Rc::into_raw` and this word owned
+        // one count of it, which `forget` hands over.
+        unsafe {
+            match object {
+                Object::Boxed(pointer) => {
+                    let boxed = Rc::from_raw(pointer);
+                    Rc::try_unwrap(boxed).unwrap_or_else(|boxed| (*boxed).clone())
+                }
+                Object::Native(pointer) => Value::Native(Rc::from_raw(pointer)),
+                Object::Userdata(pointer) => Value::Userdata(Rc::from_raw(pointer))
+            }
+        }
+    }
+}
+
+impl Clone for PackedValue {
+    fn clone(&self) -> Self {
+        // SAFETY: the pointer came from `Rc::into_raw` and is alive for as
+        // long as this word holds its count.
+        unsafe {
+            match self.object() {
+                Some(Object::Boxed(pointer)) => Rc::increment_strong_count(pointer),
+                Some(Object::Native(pointer)) => Rc::increment_strong_count(pointer),
+                Some(Object::Userdata(pointer)) => Rc::increment_strong_count(pointer),
+                None => {}
+            }
+        }
+        PackedValue(self.0)
+    }
+}
+
+impl Drop for PackedValue {
+    fn drop(&mut self) {
+        // SAFETY: as in `clone`, and this word's count goes with it.
+        unsafe {
+            match self.object() {
+                Some(Object::Boxed(pointer)) => Rc::decrement_strong_count(pointer),
+                Some(Object::Native(pointer)) => Rc::decrement_strong_count(pointer),
+                Some(Object::Userdata(pointer)) => Rc::decrement_strong_count(pointer),
+                None => {}
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PackedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", Value::from(self.clone()))
+    }
+}
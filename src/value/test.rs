@@ -0,0 +1,112 @@
+use super::*;
+
+#[test]
+fn displays_like_lox() {
+    assert_eq!(Value::Number(7.0).to_string(), "7.0");
+    assert_eq!(Value::Number(-0.0).to_string(), "-0.0");
+    assert_eq!(Value::Number(1e300).to_string(), "1e300");
+    assert_eq!(Value::Number(-1.5e21).to_string(), "-1.5e21");
+    assert_eq!(Value::Number(1e20).to_string(), "100000000000000000000.0");
+    assert_eq!(Value::Number(1e-7).to_string(), "1e-7");
+    assert_eq!(Value::Number(-2.5e-300).to_string(), "-2.5e-300");
+    assert_eq!(Value::Number(f64::MIN_POSITIVE).to_string(), "2.2250738585072014e-308");
+    assert_eq!(Value::Number(0.000001).to_string(), "0.000001");
+    assert_eq!(Value::Int(7).to_string(), "7");
+    assert_eq!(Value::Int(i64::MIN).to_string(), "-9223372036854775808");
+    assert_eq!(Value::Number(0.1).to_string(), "0.1");
+    assert_eq!(Value::Number(f64::NAN).to_string(), "nan");
+    assert_eq!(Value::Number(f64::NEG_INFINITY).to_string(), "-inf");
+    assert_eq!(Value::Bool(true).to_string(), "true");
+    assert_eq!(Value::Nil.to_string(), "nil");
+}
+
+#[test]
+fn float_modulo_keeps_precision() {
+    assert_eq!(float_arithmetic(OpCode::Modulo, 1e20, 3.0), 1.0);
+    assert_eq!(float_arithmetic(OpCode::Modulo, -1e20, 3.0), 2.0);
+    assert_eq!(float_arithmetic(OpCode::Modulo, 1e-20, 1.0), 1e-20);
+    assert_eq!(float_arithmetic(OpCode::Modulo, -1e-20, 1.0), 0.0);
+    assert_eq!(float_arithmetic(OpCode::Modulo, 1e-20, -1.0).to_string(), "-0");
+    assert_eq!(float_arithmetic(OpCode::Modulo, -7.5, 2.0), 0.5);
+    assert_eq!(float_arithmetic(OpCode::Modulo, 7.5, -2.0), -0.5);
+}
+
+#[cfg(feature = "nan_boxing")]
+#[test]
+fn packs_and_unpacks() {
+    use super::packed::PackedValue;
+
+    let values = [
+        Value::Number(1.5),
+        Value::Number(-0.0),
+        Value::Number(f64::INFINITY),
+        Value::Bool(false),
+        Value::Bool(true),
+        Value::Nil,
+        Value::Int(0),
+        Value::Int(-1),
+        Value::Int((1 << 47) - 1),
+        Value::Int(-(1 << 47)),
+        Value::Int(i64::MAX),
+        Value::Int(i64::MIN),
+        Value::String("text".into())
+    ];
+    for value in &values {
+        let packed = PackedValue::from(value.clone());
+        assert_eq!(Value::from(packed.clone()), *value);
+        assert_eq!(Value::from(packed), *value);
+    }
+
+    let nan = PackedValue::from(Value::Number(-f64::NAN));
+    assert!(nan.as_number().unwrap().is_nan());
+    assert_eq!(PackedValue::from(Value::Nil).as_number(), None);
+}
+
+#[cfg(feature = "nan_boxing")]
+#[test]
+fn packed_objects_are_shared() {
+    use super::packed::PackedValue;
+    use std::rc::Rc;
+
+    let string: Rc<str> = "shared".into();
+    let packed = PackedValue::from(Value::String(Rc::clone(&string)));
+    let copy = packed.clone();
+    assert_eq!(Rc::strong_count(&string), 2);
+
+    drop(packed);
+    assert_eq!(Value::from(copy), Value::String(Rc::clone(&string)));
+    assert_eq!(Rc::strong_count(&string), 1);
+}
+
+#[cfg(feature = "nan_boxing")]
+#[test]
+fn packs_host_objects_in_place() {
+    use super::packed::PackedValue;
+    use super::userdata::{Userdata, UserClass};
+    use std::rc::Rc;
+
+    let native = Rc::new(NativeFunction {
+        name: String::from("f"),
+        arity: 0,
+        function: Box::new(|_vm: &mut VM, _args: &[Value]| Ok(Value::Nil))
+    });
+    let userdata = Rc::new(Userdata::new(&Rc::new(UserClass::new("Counter")), 1_i64));
+
+    let packed = PackedValue::from(Value::Native(Rc::clone(&native)));
+    let copy = packed.clone();
+    assert_eq!(Rc::strong_count(&native), 3);
+    drop(packed);
+    match Value::from(copy) {
+        Value::Native(unpacked) => assert!(Rc::ptr_eq(&unpacked, &native)),
+        other => panic!("unpacked {:?}", other)
+    }
+    assert_eq!(Rc::strong_count(&native), 1);
+
+    let packed = PackedValue::from(Value::Userdata(Rc::clone(&userdata)));
+    assert_eq!(format!("{:?}", packed.clone()), format!("{:?}", Value::Userdata(Rc::clone(&userdata))));
+    match Value::from(packed) {
+        Value::Userdata(unpacked) => assert!(Rc::ptr_eq(&unpacked, &userdata)),
+        other => panic!("unpacked {:?}", other)
+    }
+    assert_eq!(Rc::strong_count(&userdata), 1);
+}
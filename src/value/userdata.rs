@@ -0,0 +1,108 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use super::*;
+
+/// A method's body. It gets the object's data, which the class's methods
+/// downcast to the Rust type they were registered for.
+pub type MethodBody = dyn Fn(&mut VM, &mut dyn Any, &[Value]) -> Result<Value, String>;
+
+type Finalizer = dyn Fn(&mut dyn Any);
+
+pub struct Method {
+    pub arity: usize,
+    pub body: Box<MethodBody>
+}
+
+/// The methods shared by userdata objects of one kind, along with what to
+/// do when one of them goes away.
+pub struct UserClass {
+    pub name: String,
+    methods: HashMap<String, Method>,
+    finalizer: Option<Box<Finalizer>>
+}
+
+impl UserClass {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            methods: HashMap::new(),
+            finalizer: None
+        }
+    }
+
+    /// Adds a method for objects holding a `T`, callable from scripts as
+    /// `object.name(...)` with `arity` arguments.
+    pub fn add_method<T, F>(&mut self, name: &str, arity: usize, body: F)
+        where T: Any, F: Fn(&mut VM, &mut T, &[Value]) -> Result<Value, String> + 'static
+    {
+        let class = self.name.clone();
+        let body = move |vm: &mut VM, data: &mut dyn Any, args: &[Value]| {
+            match data.downcast_mut::<T>() {
+                Some(data) => body(vm, data, args),
+                None => Err(format!("{} method called on the wrong kind of data.", class))
+            }
+        };
+        self.methods.insert(String::from(name), Method { arity, body: Box::new(body) });
+    }
+
+    pub fn method(&self, name: &str) -> Option<&Method> {
+        self.methods.get(name)
+    }
+
+    /// Runs `finalizer` on an object's data once the last script or host
+    /// reference to the object is dropped.
+    pub fn set_finalizer<T, F>(&mut self, finalizer: F)
+        where T: Any, F: Fn(&mut T) + 'static
+    {
+        self.finalizer = Some(Box::new(move |data: &mut dyn Any| {
+            if let Some(data) = data.downcast_mut::<T>() {
+                finalizer(data);
+            }
+        }));
+    }
+}
+
+/// A host value handed to scripts, which can only call its class's methods.
+pub struct Userdata {
+    pub class: Rc<UserClass>,
+    pub data: RefCell<Box<dyn Any>>
+}
+
+impl Userdata {
+    pub fn new<T: Any>(class: &Rc<UserClass>, data: T) -> Self {
+        Self {
+            class: Rc::clone(class),
+            data: RefCell::new(Box::new(data))
+        }
+    }
+}
+
+/// Wraps data in a new object of `class`, ready to hand to a script.
+pub fn new_userdata<T: Any>(class: &Rc<UserClass>, data: T) -> Value {
+    Value::Userdata(Rc::new(Userdata::new(class, data)))
+}
+
+impl Drop for Userdata {
+    fn drop(&mut self) {
+        if let Some(finalizer) = &self.class.finalizer {
+            finalizer(self.data.get_mut().as_mut());
+        }
+    }
+}
+
+impl fmt::Debug for Userdata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
+
+// Objects are equal only to themselves.
+impl PartialEq for Userdata {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
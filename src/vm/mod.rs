@@ -1,24 +1,152 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::chunk::*;
 use crate::value::*;
+use crate::value::convert::*;
 use crate::compiler::*;
+use slot::*;
 
+#[derive(Debug, PartialEq)]
 pub enum InterpretResult {
     Ok,
     CompileError,
-    RuntimeError
+    RuntimeError,
+    /// The fuel given by `VM::set_fuel` ran out.
+    OutOfFuel,
+    /// An `InterruptHandle` stopped the script.
+    Interrupted,
+    /// The fuel ran out with `VmConfig::suspend_on_fuel` set. The script
+    /// is kept as it was for `VM::resume`.
+    Suspended
+}
+
+/// An error raised by a script value called through `VM::call`.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Stops a running VM from any thread. The script ends before its next
+/// instruction with `InterpretResult::Interrupted`. An interrupt sent while
+/// no script is running or suspended is dropped when the next one starts.
+#[derive(Debug, Default)]
+#[derive(Clone)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Limits on the resources one script may use. Going over one stops the
+/// script with a runtime error instead of taking the host down with it.
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct VmConfig {
+    /// Most values the stack may hold at once.
+    pub max_stack: usize,
+    /// Deepest the call stack may grow, counting the script itself. Each
+    /// native or method call still running adds a frame, including those a
+    /// native makes back into the VM through `VM::call`.
+    pub max_frames: usize,
+    /// Most bytes of string data a script may handle. With nothing to tell
+    /// live strings from dead ones, every string pushed counts.
+    pub max_heap: usize,
+    /// Whether running out of fuel pauses the script, to be resumed later,
+    /// rather than ending it.
+    pub suspend_on_fuel: bool
+}
+
+impl Default for VmConfig {
+    fn default() -> VmConfig {
+        VmConfig {
+            max_stack: 64 * 256,
+            max_frames: 64,
+            max_heap: 64 * 1024 * 1024,
+            suspend_on_fuel: false
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct VM {
     chunk: Chunk,
     ip: usize,
-    stack: Vec<Value>
+    stack: Vec<Slot>,
+    config: VmConfig,
+    heap_bytes: usize,
+    fuel: Option<u64>,
+    interrupt: InterruptHandle,
+    suspended: bool,
+    // Calls into natives and methods that have not returned yet.
+    frames: usize,
+    globals: HashMap<Rc<str>, Value>
 }
 
 impl VM {
-    pub fn interpret(&mut self, source: String, out: &mut io::Write) -> InterpretResult {
+    pub fn with_config(config: VmConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Makes a host function callable from scripts as the global `name`.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = NativeFunction {
+            name: String::from(name),
+            arity,
+            function: Box::new(function)
+        };
+        self.set_global(name, Value::Native(Rc::new(native)));
+    }
+
+    /// Makes a Rust closure callable from scripts as the global `name`. It
+    /// takes its arguments as a tuple, whose length sets the arity, and a
+    /// script passing the wrong kind of value gets a runtime error:
+    ///
+    /// `vm.define_function("hypot", |(x, y): (f64, f64)| x.hypot(y));`
+    pub fn define_function<Args, R, F>(&mut self, name: &str, function: F)
+        where Args: FromLoxArgs, R: NativeResult, F: Fn(Args) -> R + 'static
+    {
+        let native = NativeFunction {
+            name: String::from(name),
+            arity: Args::ARITY,
+            function: Box::new(move |_vm: &mut VM, args: &[Value]| {
+                function(Args::from_lox_args(args)?).into_result()
+            })
+        };
+        self.set_global(name, Value::Native(Rc::new(native)));
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.into(), value);
+    }
+
+    /// Calls a script value from the host, which may itself be running
+    /// inside a native function the script called.
+    pub fn call(&mut self, callee: &Value, args: &[Value]) -> Result<Value, RuntimeError> {
+        self.call_value(callee, args).map_err(|message| RuntimeError { message })
+    }
+
+    pub fn interpret(&mut self, source: String, out: &mut dyn io::Write) -> InterpretResult {
         let mut compiler: Compiler = Compiler::new(source);
         
         let chunk = match compiler.compile() {
@@ -26,11 +154,59 @@ impl VM {
             Err(_e) => return InterpretResult::CompileError
         };
 
+        self.start(chunk, out)
+    }
+
+    /// Runs a chunk compiled elsewhere, after checking it is well formed.
+    pub fn interpret_chunk(&mut self, chunk: Chunk, out: &mut dyn io::Write) -> InterpretResult {
+        if let Err(error) = verify::verify(&chunk) {
+            eprintln!("{}", error);
+            return InterpretResult::CompileError;
+        }
+
+        self.start(chunk, out)
+    }
+
+    /// Carries on with a script that returned `InterpretResult::Suspended`,
+    /// from the instruction it stopped at. Refuel with `set_fuel` first.
+    pub fn resume(&mut self, out: &mut dyn io::Write) -> InterpretResult {
+        if !self.suspended {
+            eprintln!("No suspended script to resume.");
+            return InterpretResult::RuntimeError;
+        }
+
+        self.suspended = false;
+        self.run(out)
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Limits how many more instructions scripts may run, across calls to
+    /// `interpret`, or lifts the limit with `None`.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    // Starts a new script, dropping any suspended one.
+    fn start(&mut self, chunk: Chunk, out: &mut dyn io::Write) -> InterpretResult {
         self.ip = 0;
+        self.heap_bytes = 0;
+        self.stack.clear();
+        self.suspended = false;
         self.chunk = chunk;
-        
-        self.run(out);
-        InterpretResult::Ok
+        self.interrupt.0.store(false, Ordering::Relaxed);
+
+        self.run(out)
     }
 
     fn advance(&mut self) -> OpCode {
@@ -40,72 +216,250 @@ impl VM {
 
     fn read_constant(&self, arg: OpCode) -> Value {
         if let OpCode::OpArg(a) = arg {
-            self.chunk.constants[a]
+            self.chunk.constants[a].clone()
         } else {
             panic!("Expect an argument")
         }
     }
 
-    fn binary_op(&mut self, op: char) {
-       if let Some(opnd1) = self.stack.pop() {
-            if let Some(opnd2) = self.stack.pop() {
-                let result = match op {
-                    '+' => opnd2 + opnd1,
-                    '-' => opnd2 - opnd1,
-                    '*' => opnd2 * opnd1,
-                    '/' => opnd2 / opnd1,
-                     _  => { 0.0 }
-                };
-                self.stack.push(result);
+    fn runtime_error(&mut self, message: &str) -> InterpretResult {
+        eprintln!("{}", message);
+        eprintln!("[line {}] in script", self.chunk.lines[self.ip - 1]);
+        self.stack.clear();
+        InterpretResult::RuntimeError
+    }
+
+    fn push(&mut self, value: Value) -> Result<(), String> {
+        if self.stack.len() >= self.config.max_stack {
+            return Err(String::from("Stack overflow."));
+        }
+
+        if let Value::String(string) = &value {
+            self.heap_bytes += string.len();
+            if self.heap_bytes > self.config.max_heap {
+                return Err(String::from("Out of memory."));
+            }
+        }
+        self.stack.push(pack(value));
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Value> {
+        self.stack.pop().map(unpack)
+    }
+
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        let start = self.stack.len() - count;
+        self.stack.drain(start..).map(unpack).collect()
+    }
+
+    fn binary_op(&mut self, op: OpCode) -> Result<(), String> {
+        // Two floats take a short cut around unpacking.
+        let len = self.stack.len();
+        if len >= 2 {
+            if let (Some(left), Some(right)) = (number(&self.stack[len - 2]), number(&self.stack[len - 1])) {
+                self.stack.truncate(len - 2);
+                self.stack.push(pack(Value::Number(float_arithmetic(op, left, right))));
+                return Ok(());
+            }
+        }
+
+        let right = self.pop();
+        let left = self.pop();
+
+        match (left, right) {
+            (Some(left), Some(right)) => {
+                self.push(arithmetic(op, &left, &right)?)
             }
+            _ => Err(String::from("Operands must be numbers."))
+        }
+    }
+
+    fn bitwise_op(&mut self, op: OpCode) -> Result<(), String> {
+        let right = self.pop();
+        let left = self.pop();
+
+        match (left, right) {
+            (Some(left), Some(right)) => self.push(bitwise(op, &left, &right)?),
+            _ => Err(String::from("Operands must be integers."))
+        }
+    }
+
+    fn build_string(&mut self, count: usize) -> Result<(), String> {
+        let parts = self.pop_many(count);
+        let mut result = String::new();
+
+        for part in parts {
+            result.push_str(&part.to_string());
+        }
+        self.push(Value::String(result.into()))
+    }
+
+    fn global_op(&mut self, name: Value) -> Result<(), String> {
+        let value = match &name {
+            Value::String(name) => self.globals.get(name).cloned(),
+            _ => None
+        };
+
+        match value {
+            Some(value) => self.push(value),
+            None => Err(format!("Undefined variable '{}'.", name))
+        }
+    }
+
+    fn call_op(&mut self, count: usize) -> Result<(), String> {
+        let args = self.pop_many(count);
+        let callee = self.pop().expect("Callee below its arguments.");
+
+        let result = self.call_value(&callee, &args)?;
+        self.push(result)
+    }
+
+    fn invoke_op(&mut self, name: Value, count: usize) -> Result<(), String> {
+        let args = self.pop_many(count);
+        let userdata = match self.pop() {
+            Some(Value::Userdata(userdata)) => userdata,
+            _ => return Err(String::from("Only objects have methods."))
+        };
+
+        let name = name.to_string();
+        let class = Rc::clone(&userdata.class);
+        let method = class.method(&name)
+            .ok_or_else(|| format!("Undefined property '{}'.", name))?;
+
+        if count != method.arity {
+            return Err(format!("Expected {} arguments but got {}.", method.arity, count));
+        }
+        let mut data = userdata.data.try_borrow_mut()
+            .map_err(|_| format!("{} instance is already in use.", class.name))?;
+        let result = self.enter_frame(|vm| (method.body)(vm, data.as_mut(), &args))?;
+        drop(data);
+        self.push(result)
+    }
+
+    fn call_value(&mut self, callee: &Value, args: &[Value]) -> Result<Value, String> {
+        let native = match callee {
+            Value::Native(native) => native,
+            _ => return Err(String::from("Can only call functions and classes."))
+        };
+
+        if args.len() != native.arity {
+            return Err(format!("Expected {} arguments but got {}.", native.arity, args.len()));
         }
+        self.enter_frame(|vm| (native.function)(vm, args))
     }
 
-    fn run(&mut self, out: &mut io::Write) -> InterpretResult {
+    // Runs a call one frame deeper, counting the script as the first frame,
+    // so that natives calling back into the VM cannot recurse without end.
+    fn enter_frame<T>(&mut self, call: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        if self.frames + 1 >= self.config.max_frames {
+            return Err(String::from("Stack overflow."));
+        }
+
+        self.frames += 1;
+        let result = call(self);
+        self.frames -= 1;
+        result
+    }
+
+    fn run(&mut self, out: &mut dyn io::Write) -> InterpretResult {
 
         loop {
-            debug::disassemble_instruction(&self.chunk, self.ip);
-            println!("{:?}", self.stack);
+            if self.interrupt.0.swap(false, Ordering::Relaxed) {
+                self.stack.clear();
+                return InterpretResult::Interrupted;
+            }
+            match self.fuel {
+                Some(0) if self.config.suspend_on_fuel => {
+                    self.suspended = true;
+                    return InterpretResult::Suspended;
+                }
+                Some(0) => {
+                    self.stack.clear();
+                    return InterpretResult::OutOfFuel;
+                }
+                Some(fuel) => self.fuel = Some(fuel - 1),
+                None => {}
+            }
+
+            #[cfg(feature = "trace_execution")]
+            {
+                debug::disassemble_instruction(&self.chunk, self.ip);
+                println!("{:?}", self.stack);
+            }
 
             let instruction = self.advance();
 
-            match instruction {
+            let result = match instruction {
                 OpCode::Return => {
-                    write!(out, "{:?}", self.stack.pop().unwrap());
+                    let value = self.pop().unwrap();
+                    write!(out, "{}", value).expect("Write Failure.");
                     return InterpretResult::Ok;
                 },
 
                 OpCode::Constant => {
                     let arg = self.advance();
                     let value = self.read_constant(arg);
-                    self.stack.push(value);
+                    self.push(value)
                 },
 
                 OpCode::Negate => {
-                    if let Some(value) = self.stack.pop() {
-                        self.stack.push(-value);
+                    match self.pop() {
+                        Some(value) => negate(&value).and_then(|value| self.push(value)),
+                        None => Err(String::from("Operand must be a number."))
                     }
                 },
 
-                OpCode::Add => {
-                    self.binary_op('+');                    
+                OpCode::BitNot => {
+                    match self.pop() {
+                        Some(value) => complement(&value).and_then(|value| self.push(value)),
+                        None => Err(String::from("Operand must be an integer."))
+                    }
                 },
 
-                OpCode::Subtract => {
-                    self.binary_op('-');                    
+                OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide |
+                OpCode::Modulo | OpCode::IntDivide | OpCode::Power => self.binary_op(instruction),
+
+                OpCode::BitAnd | OpCode::BitOr | OpCode::BitXor |
+                OpCode::ShiftLeft | OpCode::ShiftRight => self.bitwise_op(instruction),
+
+                OpCode::BuildString => {
+                    match self.advance() {
+                        OpCode::OpArg(count) => self.build_string(count),
+                        _ => Ok(())
+                    }
                 },
 
-                OpCode::Multiply => {
-                    self.binary_op('*');                    
+                OpCode::GetGlobal => {
+                    let arg = self.advance();
+                    let name = self.read_constant(arg);
+                    self.global_op(name)
                 },
 
-                OpCode::Divide => {
-                    self.binary_op('/');                    
-                }
+                OpCode::Invoke => {
+                    let arg = self.advance();
+                    let name = self.read_constant(arg);
+                    match self.advance() {
+                        OpCode::OpArg(count) => self.invoke_op(name, count),
+                        _ => Ok(())
+                    }
+                },
 
+                OpCode::Call => {
+                    match self.advance() {
+                        OpCode::OpArg(count) => self.call_op(count),
+                        _ => Ok(())
+                    }
+                },
+
+                _ => Ok(())
+            };
 
-                _ => {}
+            if let Err(message) = result {
+                return self.runtime_error(&message);
             }
         }
     }
 }
+
+mod slot;
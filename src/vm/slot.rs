@@ -0,0 +1,44 @@
+use crate::value::*;
+
+// What the VM's stack holds: plain values, or NaN-boxed words with the
+// `nan_boxing` feature. Either way values go in with `pack` and come out
+// with `unpack`, and numbers can be read without unpacking.
+
+#[cfg(not(feature = "nan_boxing"))]
+pub type Slot = Value;
+
+#[cfg(not(feature = "nan_boxing"))]
+pub fn pack(value: Value) -> Slot {
+    value
+}
+
+#[cfg(not(feature = "nan_boxing"))]
+pub fn unpack(slot: Slot) -> Value {
+    slot
+}
+
+#[cfg(not(feature = "nan_boxing"))]
+pub fn number(slot: &Slot) -> Option<f64> {
+    match slot {
+        Value::Number(number) => Some(*number),
+        _ => None
+    }
+}
+
+#[cfg(feature = "nan_boxing")]
+pub use crate::value::packed::PackedValue as Slot;
+
+#[cfg(feature = "nan_boxing")]
+pub fn pack(value: Value) -> Slot {
+    Slot::from(value)
+}
+
+#[cfg(feature = "nan_boxing")]
+pub fn unpack(slot: Slot) -> Value {
+    Value::from(slot)
+}
+
+#[cfg(feature = "nan_boxing")]
+pub fn number(slot: &Slot) -> Option<f64> {
+    slot.as_number()
+}
@@ -1,4 +1,19 @@
-use rlox::vm::VM;
+use std::cell::Cell;
+use std::rc::Rc;
+
+use rlox::compiler::{Compiler, OptLevel};
+use rlox::value::Value;
+use rlox::value::userdata::{new_userdata, UserClass};
+use rlox::vm::{InterpretResult, VmConfig, VM};
+
+fn run(source: &str) -> (InterpretResult, String) {
+    let mut vm: VM = Default::default();
+    let mut out = Vec::new();
+
+    let result = vm.interpret(String::from(source), &mut out);
+
+    (result, String::from_utf8(out).unwrap())
+}
 
 #[test]
 fn expr() {
@@ -11,5 +26,423 @@ fn expr() {
 
     let output = String::from_utf8(out).unwrap();
     
-    assert_eq!(output, "7.0");
+    assert_eq!(output, "7");
+}
+
+#[test]
+fn string_escapes() {
+    let (result, output) = run(r#""tab\there\n\"quoted\" \\ \u{263A}""#);
+
+    assert_eq!(result, InterpretResult::Ok);
+    assert_eq!(output, "tab\there\n\"quoted\" \\ \u{263A}");
+}
+
+#[test]
+fn raw_string() {
+    let (result, output) = run("\"\"\"^\\d+\\n$\n\"ok\" \"\"\"");
+
+    assert_eq!(result, InterpretResult::Ok);
+    assert_eq!(output, "^\\d+\\n$\n\"ok\" ");
+}
+
+#[test]
+fn invalid_escape() {
+    let (result, output) = run(r#""bad \x escape""#);
+
+    assert_eq!(result, InterpretResult::CompileError);
+    assert_eq!(output, "");
+}
+
+#[test]
+fn negate_string() {
+    let (result, _) = run(r#"-"text""#);
+
+    assert_eq!(result, InterpretResult::RuntimeError);
+}
+
+#[test]
+fn string_interpolation() {
+    let (result, output) = run(r#""1 + 2 = ${1 + 2}, ${"nested ${"x" + "y"}"}!""#);
+
+    assert_eq!(result, InterpretResult::Ok);
+    assert_eq!(output, "1 + 2 = 3, nested xy!");
+}
+
+#[test]
+fn unterminated_interpolation() {
+    let (result, _) = run(r#""total ${1 + 2"#);
+
+    assert_eq!(result, InterpretResult::CompileError);
+}
+
+#[test]
+fn string_concatenation() {
+    let (result, output) = run(r#""con" + "cat""#);
+
+    assert_eq!(result, InterpretResult::Ok);
+    assert_eq!(output, "concat");
+}
+
+#[test]
+fn number_literals() {
+    let (result, output) = run("0xFF + 0b101 + 1_000 + 2.5e1 + 5e-1");
+
+    assert_eq!(result, InterpretResult::Ok);
+    assert_eq!(output, "1285.5");
+}
+
+#[test]
+fn malformed_number() {
+    let (result, _) = run("1 + 0x");
+
+    assert_eq!(result, InterpretResult::CompileError);
+}
+
+#[test]
+fn number_overflow() {
+    assert_eq!(run("1e309").0, InterpretResult::CompileError);
+    assert_eq!(run("0x1_0000_0000_0000_0000").0, InterpretResult::CompileError);
+}
+
+#[test]
+fn double_precision() {
+    assert_eq!(run("16777217").1, "16777217");
+    assert_eq!(run("16777216 + 1").1, "16777217");
+    assert_eq!(run("19.99 * 3").1, "59.97");
+    assert_eq!(run("0.1 + 0.2").1, "0.30000000000000004");
+    assert_eq!(run("9007199254740993.0").1, "9007199254740992.0");
+    assert_eq!(run("0xFFFF_FFFF_FFFF").1, "281474976710655");
+    assert_eq!(run("\"${123456789.125}\"").1, "123456789.125");
+}
+
+#[test]
+fn number_formatting() {
+    assert_eq!(run("7").1, "7");
+    assert_eq!(run("2.5").1, "2.5");
+    assert_eq!(run("7.0").1, "7.0");
+    assert_eq!(run("-0").1, "0");
+    assert_eq!(run("-0.0").1, "-0.0");
+    assert_eq!(run("1e300 * 10").1, "1e301");
+    assert_eq!(run("\"${1 / 1e7}\"").1, "1e-7");
+    assert_eq!(run("1 / 0").1, "inf");
+    assert_eq!(run("-1 / 0").1, "-inf");
+    assert_eq!(run("0 / 0").1, "nan");
+    assert_eq!(run("\"${0 / 0} ${1 / 0} ${-0.0}\"").1, "nan inf -0.0");
+}
+
+#[test]
+fn left_associative_operators() {
+    let (result, output) = run("10 - 2 - 3 - 16 / 4 / 2");
+
+    assert_eq!(result, InterpretResult::Ok);
+    assert_eq!(output, "3.0");
+}
+
+#[test]
+fn integer_arithmetic() {
+    assert_eq!(run("9007199254740993").1, "9007199254740993");
+    assert_eq!(run("7 ~/ 2").1, "3");
+    assert_eq!(run("-7 ~/ 2").1, "-4");
+    assert_eq!(run("7 % 3").1, "1");
+    assert_eq!(run("-7 % 3").1, "2");
+    assert_eq!(run("7 % -3").1, "-2");
+    assert_eq!(run("7 / 2").1, "3.5");
+    assert_eq!(run("6 / 2").1, "3.0");
+    assert_eq!(run("1 + 0.5").1, "1.5");
+    assert_eq!(run("7.5 % 2").1, "1.5");
+    assert_eq!(run("-7.5 % 2").1, "0.5");
+    assert_eq!(run("7.5 % -2").1, "-0.5");
+    assert_eq!(run("1e20 % 3.0").1, "1.0");
+    assert_eq!(run("7.5 ~/ 2").1, "3.0");
+    assert_eq!(run("1 + 2 * 3 % 4").1, "3");
+}
+
+#[test]
+fn integer_literals() {
+    assert_eq!(run("0xFFFF_FFFF_FFFF_FFFF").1, "-1");
+    assert_eq!(run("0x8000_0000_0000_0000").1, "-9223372036854775808");
+    assert_eq!(run("0x7FFF_FFFF_FFFF_FFFF").1, "9223372036854775807");
+    assert_eq!(run(&format!("0b1{}", "0".repeat(63))).1, "-9223372036854775808");
+    assert_eq!(run("-9223372036854775807 - 1").1, "-9223372036854775808");
+    assert_eq!(run("10000000000000000000").1, "10000000000000000000.0");
+    assert_eq!(run("0x1_0000_0000_0000_0000").0, InterpretResult::CompileError);
+}
+
+#[test]
+fn integer_errors() {
+    assert_eq!(run("9223372036854775807").1, "9223372036854775807");
+    assert_eq!(run("9223372036854775808").1, "9223372036854776000.0");
+    assert_eq!(run("9223372036854775807 + 1").0, InterpretResult::RuntimeError);
+    assert_eq!(run("-9223372036854775807 - 2").0, InterpretResult::RuntimeError);
+    assert_eq!(run("4294967296 * 4294967296").0, InterpretResult::RuntimeError);
+    assert_eq!(run("1 ~/ 0").0, InterpretResult::RuntimeError);
+    assert_eq!(run("1 % 0").0, InterpretResult::RuntimeError);
+    assert_eq!(run("1 ~/ 0.0").1, "inf");
+}
+
+#[test]
+fn exponentiation() {
+    assert_eq!(run("2 ** 10").1, "1024");
+    assert_eq!(run("2 ** 3 ** 2").1, "512");
+    assert_eq!(run("-2 ** 2").1, "-4");
+    assert_eq!(run("(-2) ** 2").1, "4");
+    assert_eq!(run("2 ** -1").1, "0.5");
+    assert_eq!(run("2 * 3 ** 2").1, "18");
+    assert_eq!(run("4 ** 0.5").1, "2.0");
+    assert_eq!(run("(-1) ** 4294967297").1, "-1");
+    assert_eq!(run("2 ** 63").0, InterpretResult::RuntimeError);
+}
+
+#[test]
+fn bitwise_operators() {
+    assert_eq!(run("0xF0 | 0x0F").1, "255");
+    assert_eq!(run("0b1100 & 0b1010").1, "8");
+    assert_eq!(run("0b1100 ^ 0b1010").1, "6");
+    assert_eq!(run("~0").1, "-1");
+    assert_eq!(run("1 << 62 << 2").1, "0");
+    assert_eq!(run("-16 >> 2").1, "-4");
+    assert_eq!(run("-1 >> 64").1, "-1");
+    assert_eq!(run("1 + 2 << 3 & 0xFF | 1").1, "25");
+    assert_eq!(run("4.0 | 1").1, "5");
+    assert_eq!(run("1.5 | 1").0, InterpretResult::RuntimeError);
+    assert_eq!(run("~\"a\"").0, InterpretResult::RuntimeError);
+    assert_eq!(run("1 << -1").0, InterpretResult::RuntimeError);
+}
+
+#[test]
+fn trailing_tokens() {
+    assert_eq!(run("1 2").0, InterpretResult::CompileError);
+}
+
+#[test]
+fn stack_limit() {
+    let config = VmConfig { max_stack: 3, ..Default::default() };
+    let compile = |source: &str| {
+        Compiler::with_opt_level(String::from(source), OptLevel::None).compile().unwrap()
+    };
+
+    let mut vm = VM::with_config(config);
+    assert_eq!(vm.interpret_chunk(compile("1 + (2 + 3)"), &mut Vec::new()), InterpretResult::Ok);
+    assert_eq!(vm.interpret_chunk(compile("1 + (2 + (3 + 4))"), &mut Vec::new()), InterpretResult::RuntimeError);
+
+    let mut out = Vec::new();
+    assert_eq!(vm.interpret(String::from("1 + (2 + (3 + 4))"), &mut out), InterpretResult::Ok);
+    assert_eq!(String::from_utf8(out).unwrap(), "10");
+}
+
+fn recurse(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match args[0] {
+        Value::Int(0) => Ok(Value::Int(0)),
+        Value::Int(n) => {
+            let callee = vm.get_global("recurse").unwrap();
+            vm.call(&callee, &[Value::Int(n - 1)]).map_err(|error| error.message)
+        }
+        _ => Err(String::from("recurse() takes an integer."))
+    }
+}
+
+#[test]
+fn frame_limit() {
+    let mut vm = VM::with_config(VmConfig { max_frames: 4, ..Default::default() });
+    vm.define_native("recurse", 1, recurse);
+
+    // The script and three calls fill the four frames.
+    assert_eq!(vm.interpret(String::from("recurse(2)"), &mut Vec::new()), InterpretResult::Ok);
+    assert_eq!(vm.interpret(String::from("recurse(3)"), &mut Vec::new()), InterpretResult::RuntimeError);
+    assert_eq!(vm.interpret(String::from("recurse(2)"), &mut Vec::new()), InterpretResult::Ok);
+
+    let mut vm: VM = Default::default();
+    vm.define_native("recurse", 1, recurse);
+    let callee = vm.get_global("recurse").unwrap();
+    assert_eq!(vm.call(&callee, &[Value::Int(1_000_000)]).unwrap_err().message, "Stack overflow.");
+    assert_eq!(vm.call(&callee, &[Value::Int(10)]), Ok(Value::Int(0)));
+}
+
+#[test]
+fn heap_limit() {
+    let mut vm = VM::with_config(VmConfig { max_heap: 8, ..Default::default() });
+
+    assert_eq!(vm.interpret(String::from(r#""12345678""#), &mut Vec::new()), InterpretResult::Ok);
+    assert_eq!(vm.interpret(String::from(r#""123456789""#), &mut Vec::new()), InterpretResult::RuntimeError);
+    assert_eq!(vm.interpret(String::from(r#""1234" + "56789""#), &mut Vec::new()), InterpretResult::RuntimeError);
+}
+
+#[test]
+fn fuel() {
+    let mut vm: VM = Default::default();
+
+    vm.set_fuel(Some(1));
+    assert_eq!(vm.interpret(String::from("1 + 2"), &mut Vec::new()), InterpretResult::OutOfFuel);
+    assert_eq!(vm.fuel(), Some(0));
+
+    vm.set_fuel(Some(4));
+    assert_eq!(vm.interpret(String::from("1 + 2"), &mut Vec::new()), InterpretResult::Ok);
+    assert_eq!(vm.fuel(), Some(2));
+
+    vm.set_fuel(None);
+    assert_eq!(vm.interpret(String::from("1 + 2"), &mut Vec::new()), InterpretResult::Ok);
+}
+
+fn stop(vm: &mut VM, _args: &[Value]) -> Result<Value, String> {
+    let handle = vm.interrupt_handle();
+    std::thread::spawn(move || handle.interrupt()).join().unwrap();
+    Ok(Value::Int(1))
+}
+
+#[test]
+fn interrupt() {
+    let mut vm: VM = Default::default();
+    vm.define_native("stop", 0, stop);
+    let mut out = Vec::new();
+
+    assert_eq!(vm.interpret(String::from("stop() + 2"), &mut out), InterpretResult::Interrupted);
+    assert_eq!(vm.interpret(String::from("1 + 2"), &mut out), InterpretResult::Ok);
+    assert_eq!(String::from_utf8(out).unwrap(), "3");
+
+    // An interrupt between scripts does not reach the next one.
+    vm.interrupt_handle().interrupt();
+    assert_eq!(vm.interpret(String::from("1 + 2"), &mut Vec::new()), InterpretResult::Ok);
+}
+
+#[test]
+fn suspend_and_resume() {
+    let config = VmConfig { suspend_on_fuel: true, ..Default::default() };
+    let chunk = Compiler::with_opt_level(String::from("(-1 + 2) * 3 - -4"), OptLevel::None).compile().unwrap();
+    let mut vm = VM::with_config(config);
+    let mut out = Vec::new();
+
+    vm.set_fuel(Some(3));
+    let mut result = vm.interpret_chunk(chunk, &mut out);
+    let mut slices = 1;
+    while result == InterpretResult::Suspended {
+        assert!(vm.is_suspended());
+        vm.set_fuel(Some(3));
+        result = vm.resume(&mut out);
+        slices += 1;
+    }
+
+    assert_eq!(result, InterpretResult::Ok);
+    assert_eq!(slices, 4);
+    assert_eq!(String::from_utf8(out).unwrap(), "7");
+    assert_eq!(vm.resume(&mut Vec::new()), InterpretResult::RuntimeError);
+}
+
+fn add(_vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [Value::Int(a), Value::Int(b)] => Ok(Value::Int(a + b)),
+        _ => Err(String::from("add() takes two integers."))
+    }
+}
+
+#[test]
+fn native_functions() {
+    let mut vm: VM = Default::default();
+    vm.define_native("add", 2, add);
+    let mut run = |source: &str| {
+        let mut out = Vec::new();
+        let result = vm.interpret(String::from(source), &mut out);
+        (result, String::from_utf8(out).unwrap())
+    };
+
+    assert_eq!(run("add(1, add(2, 3)) * 2"), (InterpretResult::Ok, String::from("12")));
+    assert_eq!(run("\"sum: ${add(1, 2)}\""), (InterpretResult::Ok, String::from("sum: 3")));
+    assert_eq!(run("add"), (InterpretResult::Ok, String::from("<native fn>")));
+    assert_eq!(run("add(1)").0, InterpretResult::RuntimeError);
+    assert_eq!(run("add(1, \"2\")").0, InterpretResult::RuntimeError);
+    assert_eq!(run("missing(1)").0, InterpretResult::RuntimeError);
+    assert_eq!(run("1(2)").0, InterpretResult::RuntimeError);
+    assert_eq!(run("add(1, 2").0, InterpretResult::CompileError);
+}
+
+fn apply(vm: &mut VM, args: &[Value]) -> Result<Value, String> {
+    vm.call(&args[0], &args[1..]).map_err(|error| error.message)
+}
+
+#[test]
+fn globals_and_host_calls() {
+    let mut vm: VM = Default::default();
+    vm.define_native("add", 2, add);
+    vm.define_native("apply", 3, apply);
+    vm.set_global("answer", Value::Int(42));
+
+    let mut out = Vec::new();
+    assert_eq!(vm.interpret(String::from("apply(add, answer, 1)"), &mut out), InterpretResult::Ok);
+    assert_eq!(String::from_utf8(out).unwrap(), "43");
+    assert_eq!(vm.interpret(String::from("apply(add, answer, \"x\")"), &mut Vec::new()),
+               InterpretResult::RuntimeError);
+
+    let add = vm.get_global("add").unwrap();
+    assert_eq!(vm.call(&add, &[Value::Int(1), Value::Int(2)]), Ok(Value::Int(3)));
+    assert_eq!(vm.call(&add, &[]).unwrap_err().message, "Expected 2 arguments but got 0.");
+    assert_eq!(vm.call(&Value::Number(1.0), &[]).unwrap_err().to_string(),
+               "Can only call functions and classes.");
+    assert_eq!(vm.get_global("missing"), None);
+}
+
+#[test]
+fn typed_functions() {
+    let mut vm: VM = Default::default();
+    vm.define_function("hypot", |(x, y): (f64, f64)| x.hypot(y));
+    vm.define_function("repeat", |(text, times): (String, i64)| text.repeat(times as usize));
+    vm.define_function("half", |(n,): (i64,)| {
+        if n % 2 == 0 { Ok(n / 2) } else { Err(format!("{} is odd.", n)) }
+    });
+    vm.define_function("name", |(): ()| "lox");
+    vm.define_function("or_default", |(text,): (Option<String>,)| text.unwrap_or_default());
+    let mut run = |source: &str| {
+        let mut out = Vec::new();
+        let result = vm.interpret(String::from(source), &mut out);
+        (result, String::from_utf8(out).unwrap())
+    };
+
+    assert_eq!(run("hypot(3, 4)"), (InterpretResult::Ok, String::from("5.0")));
+    assert_eq!(run("repeat(name(), half(4))"), (InterpretResult::Ok, String::from("loxlox")));
+    assert_eq!(run("or_default(\"x\")"), (InterpretResult::Ok, String::from("x")));
+    assert_eq!(run("half(3)").0, InterpretResult::RuntimeError);
+    assert_eq!(run("repeat(\"a\", 1.5)").0, InterpretResult::RuntimeError);
+    assert_eq!(run("hypot(3)").0, InterpretResult::RuntimeError);
+
+    let repeat = vm.get_global("repeat").unwrap();
+    assert_eq!(vm.call(&repeat, &[Value::Number(1.0), Value::Number(2.0)]).unwrap_err().message,
+               "Expected a string for argument 1 but got a number.");
+    let or_default = vm.get_global("or_default").unwrap();
+    assert_eq!(vm.call(&or_default, &[Value::Nil]), Ok(Value::String("".into())));
+    assert_eq!(vm.call(&or_default, &[Value::Bool(true)]).unwrap_err().message,
+               "Expected a string or nil for argument 1 but got a boolean.");
+}
+
+#[test]
+fn userdata() {
+    let finalized = Rc::new(Cell::new(0));
+    let mut class = UserClass::new("Counter");
+    class.add_method("add", 1, |_vm, count: &mut i64, args| {
+        if let Value::Int(n) = args[0] {
+            *count += n;
+        }
+        Ok(Value::Int(*count))
+    });
+    class.add_method("get", 0, |_vm, count: &mut i64, _args| Ok(Value::Int(*count)));
+    let seen = Rc::clone(&finalized);
+    class.set_finalizer(move |count: &mut i64| seen.set(*count));
+    let class = Rc::new(class);
+
+    let mut vm: VM = Default::default();
+    vm.set_global("counter", new_userdata(&class, 1_i64));
+    let mut run = |source: &str| {
+        let mut out = Vec::new();
+        let result = vm.interpret(String::from(source), &mut out);
+        (result, String::from_utf8(out).unwrap())
+    };
+
+    assert_eq!(run("counter.add(2) * 10 + counter.get()"), (InterpretResult::Ok, String::from("33")));
+    assert_eq!(run("\"${counter}\""), (InterpretResult::Ok, String::from("Counter instance")));
+    assert_eq!(run("counter.missing()").0, InterpretResult::RuntimeError);
+    assert_eq!(run("counter.add()").0, InterpretResult::RuntimeError);
+    assert_eq!(run("1.add(2)").0, InterpretResult::CompileError);
+    assert_eq!(run("(1).add(2)").0, InterpretResult::RuntimeError);
+    assert_eq!(run("counter.get").0, InterpretResult::CompileError);
+
+    assert_eq!(finalized.get(), 0);
+    vm.set_global("counter", Value::Nil);
+    assert_eq!(finalized.get(), 3);
 }